@@ -5,20 +5,20 @@ fn main() {
 
     use rln::poseidon::Poseidon;
 
-    let mut hasher = Poseidon::<Bn256>::new();
+    let hasher = Poseidon::<Bn256>::new();
     let input1: Vec<Fr> = ["0"].iter().map(|e| Fr::from_str(e).unwrap()).collect();
-    let r1: Fr = hasher.hash(input1.to_vec());
+    let r1: Fr = hasher.hash(input1.to_vec()).unwrap();
     let input2: Vec<Fr> = ["1", "0"]
         .iter()
         .map(|e| Fr::from_str(e).unwrap())
         .collect();
-    let r2: Fr = hasher.hash(input2.to_vec());
+    let r2: Fr = hasher.hash(input2.to_vec()).unwrap();
     // println!("{:?}", r1);
     let input3: Vec<Fr> = ["1", "2"]
         .iter()
         .map(|e| Fr::from_str(e).unwrap())
         .collect();
-    let r3: Fr = hasher.hash(input3.to_vec());
+    let r3: Fr = hasher.hash(input3.to_vec()).unwrap();
     
 
     let hash1 = to_hex(&r1);
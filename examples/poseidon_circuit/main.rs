@@ -29,8 +29,8 @@ fn main() {
         .alloc(cs.namespace(|| "hash alloc"), allocated_inputs)
         .unwrap();
     let result = res_allocated.get_value().unwrap();
-    let mut poseidon = Poseidon::<Bn256>::new();
-    let expected = poseidon.hash(inputs);
+    let poseidon = Poseidon::<Bn256>::new();
+    let expected = poseidon.hash(inputs).unwrap();
 
     
     println!("circuit hash (1,2): 0x{}", to_hex(&result));
@@ -0,0 +1,108 @@
+use crate::error::RlnError;
+use bellman::groth16::{Proof, VerifyingKey};
+use bellman::pairing::ff::{Field, PrimeField};
+use bellman::pairing::{CurveAffine, CurveProjective, Engine};
+use rand::{Rand, Rng};
+
+// Batched Groth16 verification.
+//
+// Every proof i must satisfy
+// A_i * B_i = alpha * beta + IC(x_i) * gamma + C_i * delta
+// so for random scalars r_i a valid batch satisfies
+// sum r_i A_i * B_i - (sum r_i IC(x_i)) * gamma - (sum r_i C_i) * delta = (sum r_i) alpha * beta
+// which costs one miller loop term per proof plus two, and a single final exponentiation.
+// A failing batch is bisected to find the invalid proofs.
+
+pub fn verify_batch<E: Engine, G: Rng>(
+    vk: &VerifyingKey<E>,
+    proofs: &[(Proof<E>, Vec<E::Fr>)],
+    rng: &mut G,
+) -> Result<Vec<bool>, RlnError> {
+    let mut neg_gamma = vk.gamma_g2;
+    neg_gamma.negate();
+    let mut neg_delta = vk.delta_g2;
+    neg_delta.negate();
+    let ctx = BatchCtx {
+        vk,
+        alpha_g1_beta_g2: E::pairing(vk.alpha_g1, vk.beta_g2),
+        neg_gamma_g2: neg_gamma.prepare(),
+        neg_delta_g2: neg_delta.prepare(),
+    };
+    let mut results = vec![false; proofs.len()];
+    // proofs with a wrong number of public inputs are invalid without pairing them
+    let (sized, malformed): (Vec<_>, Vec<_>) =
+        (0..proofs.len()).partition(|i| proofs[*i].1.len() + 1 == vk.ic.len());
+    if malformed.is_empty() {
+        ctx.bisect(proofs, &mut results, rng);
+        return Ok(results);
+    }
+    let sized_proofs: Vec<_> = sized.iter().map(|i| proofs[*i].clone()).collect();
+    let mut sized_results = vec![false; sized.len()];
+    ctx.bisect(&sized_proofs, &mut sized_results, rng);
+    for (i, valid) in sized.into_iter().zip(sized_results) {
+        results[i] = valid;
+    }
+    Ok(results)
+}
+
+struct BatchCtx<'a, E: Engine> {
+    vk: &'a VerifyingKey<E>,
+    alpha_g1_beta_g2: E::Fqk,
+    neg_gamma_g2: <E::G2Affine as CurveAffine>::Prepared,
+    neg_delta_g2: <E::G2Affine as CurveAffine>::Prepared,
+}
+
+impl<'a, E: Engine> BatchCtx<'a, E> {
+    fn bisect<G: Rng>(&self, proofs: &[(Proof<E>, Vec<E::Fr>)], results: &mut [bool], rng: &mut G) {
+        if proofs.is_empty() {
+            return;
+        }
+        if self.check(proofs, rng) {
+            results.iter_mut().for_each(|r| *r = true);
+            return;
+        }
+        if proofs.len() == 1 {
+            return;
+        }
+        let mid = proofs.len() / 2;
+        let (results_l, results_r) = results.split_at_mut(mid);
+        self.bisect(&proofs[..mid], results_l, rng);
+        self.bisect(&proofs[mid..], results_r, rng);
+    }
+
+    fn check<G: Rng>(&self, proofs: &[(Proof<E>, Vec<E::Fr>)], rng: &mut G) -> bool {
+        let mut acc_ic = E::G1::zero();
+        let mut acc_c = E::G1::zero();
+        let mut r_sum = E::Fr::zero();
+        let mut prepared = Vec::with_capacity(proofs.len());
+
+        for (proof, public_inputs) in proofs.iter() {
+            let r = E::Fr::rand(rng);
+            r_sum.add_assign(&r);
+
+            let mut ic = self.vk.ic[0].into_projective();
+            for (x, b) in public_inputs.iter().zip(self.vk.ic.iter().skip(1)) {
+                ic.add_assign(&b.mul(x.into_repr()));
+            }
+            ic.mul_assign(r.into_repr());
+            acc_ic.add_assign(&ic);
+
+            acc_c.add_assign(&proof.c.mul(r.into_repr()));
+
+            let ra = proof.a.mul(r.into_repr()).into_affine();
+            prepared.push((ra.prepare(), proof.b.prepare()));
+        }
+
+        let acc_ic = acc_ic.into_affine().prepare();
+        let acc_c = acc_c.into_affine().prepare();
+        let mut terms: Vec<_> = prepared.iter().map(|(a, b)| (a, b)).collect();
+        terms.push((&acc_ic, &self.neg_gamma_g2));
+        terms.push((&acc_c, &self.neg_delta_g2));
+
+        let lhs = match E::final_exponentiation(&E::miller_loop(terms.iter())) {
+            Some(lhs) => lhs,
+            None => return false,
+        };
+        lhs == self.alpha_g1_beta_g2.pow(r_sum.into_repr())
+    }
+}
@@ -1,13 +1,18 @@
+use crate::circuit::hasher::HasherGadget;
 use crate::circuit::poseidon::PoseidonCircuit;
 use crate::circuit::rln::{RLNCircuit, RLNInputs};
-use crate::merkle::MerkleTree;
+use crate::circuit::rln_v2::{RLNV2Circuit, RLNV2Inputs};
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::merkle::{DenseMerkleTree, MembershipTree, MerkleTree};
 use crate::poseidon::{Poseidon as PoseidonHasher, PoseidonParams};
 
 use rand::{Rand, SeedableRng, XorShiftRng};
 use sapling_crypto::bellman::groth16::*;
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
-use sapling_crypto::bellman::Circuit;
+use sapling_crypto::bellman::{Circuit, ConstraintSystem};
+use sapling_crypto::circuit::num::AllocatedNum;
 use sapling_crypto::circuit::test::TestConstraintSystem;
 use std::error::Error;
 use std::io::{self, ErrorKind, Read, Write};
@@ -36,6 +41,161 @@ pub fn run_rln_prover_bench<E: Engine>(
     RLNTest::<E>::new(merkle_depth).run_prover_bench()
 }
 
+pub struct PoseidonBenchResult {
+    pub width: usize,
+    pub dense_time: f64,
+    pub sparse_time: f64,
+    pub dense_constraints: usize,
+    pub sparse_constraints: usize,
+}
+
+// native permutations with the dense and the sparse partial rounds,
+// and constraints of a hash of t - 1 inputs with each circuit
+pub fn run_poseidon_bench<E: Engine>(t: usize, permutations: usize) -> PoseidonBenchResult {
+    let params = PoseidonParams::<E>::cached(t).unwrap();
+    let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let inputs: Vec<E::Fr> = (1..t).map(|_| E::Fr::rand(&mut rng)).collect();
+
+    let mut dense = vec![E::Fr::zero(); t];
+    let now = Instant::now();
+    for _ in 0..permutations {
+        params.permute_dense(&mut dense).unwrap();
+    }
+    let dense_time = now.elapsed().as_secs_f64();
+
+    let mut sparse = vec![E::Fr::zero(); t];
+    let now = Instant::now();
+    for _ in 0..permutations {
+        params.permute(&mut sparse).unwrap();
+    }
+    let sparse_time = now.elapsed().as_secs_f64();
+    assert_eq!(dense, sparse);
+
+    let expected = PoseidonHasher::<E>::new().hash(inputs.clone()).unwrap();
+    let synthesize = |mut circuit: PoseidonCircuit<E>| {
+        let mut cs = TestConstraintSystem::<E>::new();
+        let allocated = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let result = circuit.alloc(cs.namespace(|| "hash"), allocated).unwrap();
+        assert_eq!(result.get_value().unwrap(), expected);
+        assert!(cs.is_satisfied());
+        cs.num_constraints()
+    };
+
+    PoseidonBenchResult {
+        width: t,
+        dense_time,
+        sparse_time,
+        dense_constraints: synthesize(PoseidonCircuit::<E>::dense()),
+        sparse_constraints: synthesize(PoseidonCircuit::<E>::new()),
+    }
+}
+
+pub struct MerkleBenchResult {
+    pub leaves: usize,
+    pub sparse_memory: usize,
+    pub dense_memory: usize,
+    // leaf updates per second
+    pub sparse_throughput: f64,
+    pub dense_throughput: f64,
+}
+
+// updates of the first leaves of a tree with each storage,
+// with the memory they take afterwards
+pub fn run_merkle_bench<E: Engine>(merkle_depth: usize, leaves: usize) -> MerkleBenchResult {
+    let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let values: Vec<E::Fr> = (0..leaves).map(|_| E::Fr::rand(&mut rng)).collect();
+
+    fn fill<E: Engine, T: MembershipTree<E>>(tree: &mut T, values: &[E::Fr]) -> f64 {
+        let now = Instant::now();
+        for (i, value) in values.iter().enumerate() {
+            tree.update(i, *value).unwrap();
+        }
+        values.len() as f64 / now.elapsed().as_secs_f64()
+    }
+
+    let hasher = PoseidonHasher::<E>::new();
+    let mut sparse = MerkleTree::empty(hasher.clone(), merkle_depth).unwrap();
+    let sparse_throughput = fill(&mut sparse, &values);
+    let mut dense = DenseMerkleTree::empty(hasher, merkle_depth).unwrap();
+    let dense_throughput = fill(&mut dense, &values);
+    assert_eq!(sparse.root(), dense.root());
+
+    MerkleBenchResult {
+        leaves,
+        sparse_memory: sparse.memory(),
+        dense_memory: dense.memory(),
+        sparse_throughput,
+        dense_throughput,
+    }
+}
+
+pub struct HasherBenchResult {
+    pub constraints: usize,
+    pub prover_time: f64,
+}
+
+// RLN circuit with every hash taken by the given hasher
+pub fn run_rln_hasher_bench<E: Engine, H: HasherGadget<E>>(
+    merkle_depth: usize,
+    gadget: H,
+) -> HasherBenchResult {
+    let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let hasher = gadget.native();
+    let mut membership_tree = MerkleTree::empty(hasher.clone(), merkle_depth).unwrap();
+    let id_key = E::Fr::rand(&mut rng);
+    let id_index = 6;
+    membership_tree
+        .update(id_index, hasher.hash(vec![id_key]).unwrap())
+        .unwrap();
+
+    let epoch = E::Fr::rand(&mut rng);
+    let share_x = E::Fr::rand(&mut rng);
+    let rln_identifier = RLNTest::<E>::rln_identifier();
+    let inputs = RLNInputs::from_identity(
+        id_key,
+        &membership_tree,
+        id_index,
+        epoch,
+        rln_identifier,
+        share_x,
+    )
+    .unwrap();
+    inputs.check_witness(&hasher).unwrap();
+
+    let circuit = RLNCircuit {
+        inputs: inputs.clone(),
+        hasher: gadget.clone(),
+    };
+    let mut cs = TestConstraintSystem::<E>::new();
+    circuit.clone().synthesize(&mut cs).unwrap();
+    assert!(cs.is_satisfied());
+    let constraints = cs.num_constraints();
+
+    let empty = RLNCircuit {
+        inputs: RLNInputs::empty(merkle_depth),
+        hasher: gadget,
+    };
+    let parameters = generate_random_parameters(empty, &mut rng).unwrap();
+    let now = Instant::now();
+    let proof = create_random_proof(circuit, &parameters, &mut rng).unwrap();
+    let prover_time = now.elapsed().as_millis() as f64 / 1000.0;
+    let verifing_key = prepare_verifying_key(&parameters.vk);
+    assert!(
+        verify_proof(&verifing_key, &proof, &inputs.public_inputs().unwrap()).unwrap(),
+        "invalid proof"
+    );
+
+    HasherBenchResult {
+        constraints,
+        prover_time,
+    }
+}
+
 pub struct RLNTest<E>
 where
     E: Engine,
@@ -58,6 +218,7 @@ where
             share_x: None,
             share_y: None,
             epoch: None,
+            rln_identifier: None,
             nullifier: None,
             root: None,
             id_key: None,
@@ -67,7 +228,7 @@ where
 
     pub fn new(merkle_depth: usize) -> RLNTest<E> {
         RLNTest {
-            rln: RLN::new(merkle_depth),
+            rln: RLN::new(merkle_depth, Self::rln_identifier()).unwrap(),
             merkle_depth,
         }
     }
@@ -76,28 +237,34 @@ where
         self.rln.hasher()
     }
 
+    pub fn rln_identifier() -> E::Fr {
+        E::Fr::from_str("100").unwrap()
+    }
+
     pub fn valid_inputs(&self) -> RLNInputs<E> {
         let mut rng = Self::rng();
-        let mut hasher = self.rln.hasher();
+        let hasher = self.rln.hasher();
 
         // Initialize empty merkle tree
         let merkle_depth = self.merkle_depth;
-        let mut membership_tree = MerkleTree::empty(hasher.clone(), merkle_depth);
+        let mut membership_tree = MerkleTree::empty(hasher.clone(), merkle_depth).unwrap();
 
         // A. setup an identity
 
         let id_key = E::Fr::rand(&mut rng);
-        let id_comm: E::Fr = hasher.hash(vec![id_key.clone()]);
+        let id_comm: E::Fr = hasher.hash(vec![id_key]).unwrap();
 
         // B. insert to the membership tree
 
         let id_index = 6; // any number below 2^depth will work
-        membership_tree.update(id_index, id_comm);
+        membership_tree.update(id_index, id_comm).unwrap();
 
         // C.1 get membership witness
 
-        let auth_path = membership_tree.witness(id_index);
-        assert!(membership_tree.check_inclusion(auth_path.clone(), id_index, id_key.clone()));
+        let auth_path = membership_tree.witness(id_index).unwrap();
+        assert!(membership_tree
+            .check_inclusion(auth_path.clone(), id_index, id_key)
+            .unwrap());
 
         // C.2 prepare sss
 
@@ -109,8 +276,10 @@ where
         let share_x = signal_hash.clone();
 
         // calculate current line equation
+        let rln_identifier = Self::rln_identifier();
+        let external_nullifier = hasher.hash(vec![epoch, rln_identifier]).unwrap();
         let a_0 = id_key.clone();
-        let a_1: E::Fr = hasher.hash(vec![a_0, epoch]);
+        let a_1: E::Fr = hasher.hash(vec![a_0, external_nullifier]).unwrap();
 
         // evaluate line equation
         let mut share_y = a_1.clone();
@@ -118,7 +287,7 @@ where
         share_y.add_assign(&a_0);
 
         // calculate nullfier
-        let nullifier = hasher.hash(vec![a_1]);
+        let nullifier = hasher.hash(vec![a_1]).unwrap();
 
         // compose the circuit
 
@@ -126,6 +295,7 @@ where
             share_x: Some(share_x),
             share_y: Some(share_y),
             epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
             nullifier: Some(nullifier),
             root: Some(membership_tree.root()),
             id_key: Some(id_key),
@@ -193,8 +363,143 @@ where
         }
     }
 
-    pub fn export_circuit_parameters<W: Write>(&self, w: W) -> io::Result<()> {
+    pub fn export_circuit_parameters<W: Write>(&self, w: W) -> Result<(), RlnError> {
         self.rln.export_circuit_parameters(w)
     }
 }
 
+
+pub struct RLNV2Test<E>
+where
+    E: Engine,
+{
+    circuit_parameters: Parameters<E>,
+    merkle_depth: usize,
+    user_message_limit: u64,
+}
+
+impl<E> RLNV2Test<E>
+where
+    E: Engine,
+{
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654])
+    }
+
+    fn fr(n: u64) -> E::Fr {
+        E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(n)).unwrap()
+    }
+
+    pub fn new(merkle_depth: usize, user_message_limit: u64) -> RLNV2Test<E> {
+        let circuit = RLNV2Circuit::<E> {
+            inputs: RLNV2Inputs::<E>::empty(merkle_depth),
+            hasher: PoseidonCircuit::<E>::new(),
+        };
+        let mut rng = Self::rng();
+        RLNV2Test {
+            circuit_parameters: generate_random_parameters(circuit, &mut rng).unwrap(),
+            merkle_depth,
+            user_message_limit,
+        }
+    }
+
+    pub fn valid_inputs(&self, message_id: u64) -> RLNV2Inputs<E> {
+        let mut rng = Self::rng();
+        let hasher = PoseidonHasher::<E>::new();
+        let mut membership_tree = MerkleTree::empty(hasher.clone(), self.merkle_depth).unwrap();
+
+        let id_key = E::Fr::rand(&mut rng);
+        let user_message_limit = Self::fr(self.user_message_limit);
+        let rate_commitment =
+            RLNV2Inputs::<E>::rate_commitment(&hasher, id_key, user_message_limit).unwrap();
+        let id_index = 6;
+        membership_tree.update(id_index, rate_commitment).unwrap();
+
+        let epoch = E::Fr::rand(&mut rng);
+        let share_x = E::Fr::rand(&mut rng);
+
+        RLNV2Inputs::from_identity(
+            id_key,
+            user_message_limit,
+            Self::fr(message_id),
+            &membership_tree,
+            id_index,
+            epoch,
+            RLNTest::<E>::rln_identifier(),
+            share_x,
+        )
+        .unwrap()
+    }
+
+    pub fn synthesize(&self, message_id: u64) -> usize {
+        let circuit = RLNV2Circuit::<E> {
+            inputs: self.valid_inputs(message_id),
+            hasher: PoseidonCircuit::<E>::new(),
+        };
+
+        let mut cs = TestConstraintSystem::<E>::new();
+        circuit.synthesize(&mut cs).unwrap();
+        let unsatisfied = cs.which_is_unsatisfied();
+        if unsatisfied.is_some() {
+            panic!("unsatisfied\n{}", unsatisfied.unwrap());
+        }
+        let unconstrained = cs.find_unconstrained();
+        if !unconstrained.is_empty() {
+            panic!("unconstrained\n{}", unconstrained);
+        }
+        assert!(cs.is_satisfied());
+        cs.num_constraints()
+    }
+
+    pub fn run_prover_bench(&self, message_id: u64) -> ProverBenchResult {
+        let inputs = self.valid_inputs(message_id);
+        inputs.check_witness(&PoseidonHasher::<E>::new()).unwrap();
+        let circuit = RLNV2Circuit::<E> {
+            inputs: inputs.clone(),
+            hasher: PoseidonCircuit::<E>::new(),
+        };
+
+        let mut rng = Self::rng();
+        let now = Instant::now();
+        let proof = create_random_proof(circuit, &self.circuit_parameters, &mut rng).unwrap();
+        let prover_time = now.elapsed().as_millis() as f64 / 1000.0;
+
+        let verifing_key = prepare_verifying_key(&self.circuit_parameters.vk);
+        assert!(
+            verify_proof(&verifing_key, &proof, &inputs.public_inputs().unwrap()).unwrap(),
+            "invalid proof"
+        );
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        self.circuit_parameters.write(&mut circuit_parameters).unwrap();
+        let prover_key_size = circuit_parameters.len();
+
+        ProverBenchResult {
+            prover_time,
+            prover_key_size,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "bench"))]
+mod test {
+    use super::run_merkle_bench;
+    use sapling_crypto::bellman::pairing::bn256::Bn256;
+
+    #[test]
+    fn test_merkle_bench() {
+        let results = vec![
+            run_merkle_bench::<Bn256>(10, 1 << 10),
+            run_merkle_bench::<Bn256>(12, 1 << 12),
+            run_merkle_bench::<Bn256>(20, 1 << 10),
+        ];
+        for result in results.iter() {
+            println!("leaves: {}", result.leaves);
+            println!("sparse memory:\t{}", result.sparse_memory);
+            println!("dense memory:\t{}", result.dense_memory);
+            println!("sparse updates/s:\t{:.0}", result.sparse_throughput);
+            println!("dense updates/s:\t{:.0}", result.dense_throughput);
+        }
+        assert!(results[1].dense_memory < results[1].sparse_memory);
+    }
+}
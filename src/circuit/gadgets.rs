@@ -0,0 +1,235 @@
+use crate::circuit::polynomial::allocate_add_with_coeff;
+use crate::circuit::hasher::HasherGadget;
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{ConstraintSystem, LinearCombination, SynthesisError};
+use sapling_crypto::circuit::{boolean, num, Assignment};
+
+// Building blocks of the RLN circuits,
+// public so that custom circuits share one implementation of them.
+
+// enforces a == b
+pub fn enforce_equal<E, CS>(
+    mut cs: CS,
+    a: &num::AllocatedNum<E>,
+    b: &num::AllocatedNum<E>,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    cs.enforce(
+        || "enforce equal",
+        |lc| lc + a.get_variable(),
+        |lc| lc + CS::one(),
+        |lc| lc + b.get_variable(),
+    );
+    Ok(())
+}
+
+// root of the tree holding leaf under the authentication path,
+// each path element comes with the position bit of the node it is hashed with
+pub fn merkle_root<E, CS, H>(
+    mut cs: CS,
+    hasher: &mut H,
+    leaf: &num::AllocatedNum<E>,
+    auth_path: &[Option<(E::Fr, bool)>],
+) -> Result<num::AllocatedNum<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+    H: HasherGadget<E>,
+{
+    let mut acc = leaf.clone();
+
+    for (i, e) in auth_path.iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("auth path {}", i));
+        let position = boolean::Boolean::from(boolean::AllocatedBit::alloc(
+            cs.namespace(|| "position bit"),
+            e.map(|e| e.1),
+        )?);
+        let path_element =
+            num::AllocatedNum::alloc(cs.namespace(|| "path element"), || Ok(e.get()?.0))?;
+
+        let (xr, xl) = num::AllocatedNum::conditionally_reverse(
+            cs.namespace(|| "conditional reversal of preimage"),
+            &acc,
+            &path_element,
+            &position,
+        )?;
+
+        acc = hasher.alloc(cs.namespace(|| "hash couple"), vec![xl, xr])?;
+    }
+    Ok(acc)
+}
+
+// root == merkle_root(leaf, auth_path)
+pub fn merkle_membership<E, CS, H>(
+    mut cs: CS,
+    hasher: &mut H,
+    leaf: &num::AllocatedNum<E>,
+    auth_path: &[Option<(E::Fr, bool)>],
+    root: &num::AllocatedNum<E>,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+    H: HasherGadget<E>,
+{
+    let acc = merkle_root(cs.namespace(|| "merkle root"), hasher, leaf, auth_path)?;
+    enforce_equal(cs.namespace(|| "enforce membership"), &acc, root)
+}
+
+// share_y == a_0 + a_1 * share_x
+pub fn share_evaluation<E, CS>(
+    mut cs: CS,
+    a_0: &num::AllocatedNum<E>,
+    a_1: &num::AllocatedNum<E>,
+    share_x: &num::AllocatedNum<E>,
+    share_y: &num::AllocatedNum<E>,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let eval = allocate_add_with_coeff(cs.namespace(|| "eval"), a_1, share_x, a_0)?;
+    enforce_equal(cs.namespace(|| "enforce lookup"), share_y, &eval)
+}
+
+// nullifier == hash(a_1)
+pub fn nullifier<E, CS, H>(
+    mut cs: CS,
+    hasher: &mut H,
+    a_1: &num::AllocatedNum<E>,
+    nullifier: &num::AllocatedNum<E>,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+    H: HasherGadget<E>,
+{
+    let nullifier_calculated =
+        hasher.alloc(cs.namespace(|| "calculated nullifier"), vec![a_1.clone()])?;
+    enforce_equal(
+        cs.namespace(|| "enforce nullifier"),
+        &nullifier_calculated,
+        nullifier,
+    )
+}
+
+// enforces that lc equals the little endian bits allocated from v,
+// so that v is below 2^bits
+pub fn range_check<E, CS>(
+    mut cs: CS,
+    lc: LinearCombination<E>,
+    v: Option<E::Fr>,
+    bits: usize,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let repr = v.map(|v| v.into_repr());
+    let mut packed = LinearCombination::<E>::zero();
+    let mut coeff = E::Fr::one();
+    for i in 0..bits {
+        let bit = boolean::AllocatedBit::alloc(
+            cs.namespace(|| format!("bit {}", i)),
+            repr.map(|r| (r.as_ref()[i / 64] >> (i % 64)) & 1 == 1),
+        )?;
+        packed = packed + (coeff, bit.get_variable());
+        coeff.double();
+    }
+    cs.enforce(|| "pack bits", |_| packed, |lc| lc + CS::one(), |_| lc);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merkle_membership, nullifier, range_check, share_evaluation};
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::merkle::MerkleTree;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+    use sapling_crypto::bellman::{ConstraintSystem, LinearCombination};
+    use sapling_crypto::circuit::num::AllocatedNum;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    fn alloc(cs: &mut TestConstraintSystem<Bn256>, name: &str, v: Fr) -> AllocatedNum<Bn256> {
+        AllocatedNum::alloc(cs.namespace(|| name), || Ok(v)).unwrap()
+    }
+
+    #[test]
+    fn test_gadgets() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let circuit_hasher = PoseidonCircuit::<Bn256>::new();
+
+        // a custom circuit proving knowledge of a leaf preimage and of a line through a point
+        let leaf = Fr::rand(&mut rng);
+        let mut tree = MerkleTree::empty(hasher.clone(), 4).unwrap();
+        tree.update(9, leaf).unwrap();
+        let auth_path: Vec<_> = tree.witness(9).unwrap().into_iter().map(Some).collect();
+
+        let a_0 = Fr::rand(&mut rng);
+        let a_1 = Fr::rand(&mut rng);
+        let share_x = Fr::rand(&mut rng);
+        let mut share_y = a_1;
+        share_y.mul_assign(&share_x);
+        share_y.add_assign(&a_0);
+
+        let build = |root: Fr, share_y: Fr, nullifier_value: Fr, small: Fr| {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let mut circuit_hasher = circuit_hasher.clone();
+            let leaf = alloc(&mut cs, "leaf", leaf);
+            let root = alloc(&mut cs, "root", root);
+            let a_0 = alloc(&mut cs, "a_0", a_0);
+            let a_1 = alloc(&mut cs, "a_1", a_1);
+            let share_x = alloc(&mut cs, "share x", share_x);
+            let share_y = alloc(&mut cs, "share y", share_y);
+            let nullifier_value = alloc(&mut cs, "nullifier", nullifier_value);
+            let small = alloc(&mut cs, "small", small);
+            merkle_membership(
+                cs.namespace(|| "membership"),
+                &mut circuit_hasher,
+                &leaf,
+                &auth_path,
+                &root,
+            )
+            .unwrap();
+            share_evaluation(cs.namespace(|| "share"), &a_0, &a_1, &share_x, &share_y).unwrap();
+            nullifier(
+                cs.namespace(|| "nullifier check"),
+                &mut circuit_hasher,
+                &a_1,
+                &nullifier_value,
+            )
+            .unwrap();
+            range_check(
+                cs.namespace(|| "range"),
+                LinearCombination::zero() + small.get_variable(),
+                small.get_value(),
+                8,
+            )
+            .unwrap();
+            cs.is_satisfied()
+        };
+
+        let root = tree.root();
+        let nullifier_value = hasher.hash(vec![a_1]).unwrap();
+        let small = Fr::from_str("255").unwrap();
+        let other = Fr::rand(&mut rng);
+        assert!(build(root, share_y, nullifier_value, small));
+        assert!(!build(other, share_y, nullifier_value, small));
+        assert!(!build(root, other, nullifier_value, small));
+        assert!(!build(root, share_y, other, small));
+        assert!(!build(
+            root,
+            share_y,
+            nullifier_value,
+            Fr::from_str("256").unwrap()
+        ));
+    }
+}
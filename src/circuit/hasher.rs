@@ -0,0 +1,92 @@
+use crate::hasher::Hasher;
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
+use sapling_crypto::circuit::num;
+
+// In-circuit counterpart of a Hasher, digests of alloc must equal
+// the ones of the native hasher for the same inputs.
+pub trait HasherGadget<E: Engine>: Clone {
+    type Native: Hasher<E>;
+
+    // native hasher computing the same digests
+    fn native(&self) -> Self::Native;
+
+    fn alloc<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: CS,
+        inputs: Vec<num::AllocatedNum<E>>,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError>;
+}
+
+// Hashes random inputs of each arity with the gadget and its native
+// hasher, panics unless the digests are equal and every constraint is
+// satisfied. Meant for tests of Hasher and HasherGadget implementations.
+#[cfg(any(test, feature = "bench"))]
+pub fn check_hasher_consistency<E, H>(gadget: &H, arities: &[usize], samples: usize)
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::pairing::ff::Field;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let native = gadget.native();
+    for n in arities.iter().cloned() {
+        for sample in 0..samples {
+            // zeros first, then random inputs
+            let inputs: Vec<E::Fr> = (0..n)
+                .map(|_| match sample {
+                    0 => E::Fr::zero(),
+                    _ => E::Fr::rand(&mut rng),
+                })
+                .collect();
+
+            let mut cs = TestConstraintSystem::<E>::new();
+            let allocated = inputs
+                .iter()
+                .enumerate()
+                .map(|(i, e)| {
+                    num::AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let digest = gadget
+                .clone()
+                .alloc(cs.namespace(|| "hash"), allocated)
+                .unwrap();
+
+            let expected = native.hash(inputs).unwrap();
+            assert_eq!(
+                digest.get_value(),
+                Some(expected),
+                "digests differ for {} inputs",
+                n
+            );
+            if let Some(unsatisfied) = cs.which_is_unsatisfied() {
+                panic!("unsatisfied for {} inputs\n{}", n, unsatisfied);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_hasher_consistency() {
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::circuit::poseidon2::Poseidon2Circuit;
+    use crate::poseidon::MAX_WIDTH;
+    use crate::poseidon2;
+    use sapling_crypto::bellman::pairing::bls12_381::Bls12;
+    use sapling_crypto::bellman::pairing::bn256::Bn256;
+
+    let arities: Vec<usize> = (1..MAX_WIDTH).collect();
+    let poseidon2_arities: Vec<usize> = (1..poseidon2::MAX_WIDTH).collect();
+
+    check_hasher_consistency(&PoseidonCircuit::<Bn256>::new(), &arities, 2);
+    check_hasher_consistency(&PoseidonCircuit::<Bn256>::dense(), &[1, 2, 4], 2);
+    check_hasher_consistency(&Poseidon2Circuit::<Bn256>::new(), &poseidon2_arities, 3);
+
+    check_hasher_consistency(&PoseidonCircuit::<Bls12>::new(), &[1, 2, 3], 2);
+    check_hasher_consistency(&Poseidon2Circuit::<Bls12>::new(), &poseidon2_arities, 2);
+}
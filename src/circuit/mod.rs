@@ -1,7 +1,13 @@
+pub mod gadgets;
+pub mod hasher;
 pub mod polynomial;
 pub mod poseidon;
+pub mod poseidon2;
 
 pub mod rln;
+pub mod rln_poly;
+pub mod rln_signal;
+pub mod rln_v2;
 
 
 #[cfg(any(test, feature = "bench"))]
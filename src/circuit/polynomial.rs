@@ -44,3 +44,23 @@ where
     );
     Ok(y)
 }
+
+// horner evaluation of a general polynomial
+// y = a_0 + a_1 * x + ... + a_k * x^k
+// from coefficients in increasing degree
+pub fn allocate_horner<CS, E>(
+    mut cs: CS,
+    coeffs: &[num::AllocatedNum<E>],
+    x: &num::AllocatedNum<E>,
+) -> Result<num::AllocatedNum<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let (a_k, lower) = coeffs.split_last().ok_or(SynthesisError::Unsatisfiable)?;
+    let mut acc = a_k.clone();
+    for (i, a_i) in lower.iter().enumerate().rev() {
+        acc = allocate_add_with_coeff(cs.namespace(|| format!("horner step {}", i)), &acc, x, a_i)?;
+    }
+    Ok(acc)
+}
@@ -1,8 +1,11 @@
-use crate::poseidon::{Poseidon as PoseidonHasher, PoseidonParams};
-use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, to_hex};
+use crate::circuit::hasher::HasherGadget;
+use crate::error::RlnError;
+use crate::poseidon::{Poseidon as PoseidonHasher, PoseidonParams, MAX_WIDTH};
+use sapling_crypto::bellman::pairing::ff::{to_hex, Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{Circuit, ConstraintSystem, LinearCombination, SynthesisError};
 use sapling_crypto::circuit::{boolean, ecc, num, Assignment};
+use std::sync::Arc;
 
 #[derive(Clone)]
 struct Element<E>
@@ -39,7 +42,8 @@ pub struct PoseidonCircuit<E>
 where
     E: Engine,
 {
-    params: PoseidonParams<E>,
+    params: Arc<PoseidonParams<E>>,
+    dense: bool,
 }
 
 impl<E> Element<E>
@@ -78,13 +82,13 @@ where
         self.an = None;
     }
 
-    pub fn num(&self) -> num::Num<E> {
+    pub fn num(&self) -> Result<num::Num<E>, SynthesisError> {
         if let Some(nu) = self.nu.clone() {
-            nu
+            Ok(nu)
         } else {
             match self.an.clone() {
-                Some(an) => num::Num::from(an),
-                None => panic!("element not exist"),
+                Some(an) => Ok(num::Num::from(an)),
+                None => Err(SynthesisError::Unsatisfiable),
             }
         }
     }
@@ -107,7 +111,7 @@ where
                 );
                 Ok(v)
             }
-            None => panic!(""),
+            None => self.an.clone().ok_or(SynthesisError::Unsatisfiable),
         }
     }
 
@@ -162,7 +166,7 @@ where
     pub fn round_constant(&self, i: Option<usize>) -> E::Fr {
         match i {
             Some(i) => self.params.round_constant(self.number * self.width() + i),
-            None => self.params.round_constant(self.number)
+            None => self.params.round_constant(self.number),
         }
     }
 
@@ -218,7 +222,9 @@ where
         mut cs: CS,
         ctx: &mut RoundCtx<E>,
     ) -> Result<(), SynthesisError> {
-        assert_eq!(ctx.width(), self.elements.len());
+        if ctx.width() != self.elements.len() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
 
         for i in 0..if ctx.is_full_round() { ctx.width() } else { 1 } {
             let round_constant = ctx.round_constant(Some(i));
@@ -268,48 +274,50 @@ where
         &mut self,
         ctx: &mut RoundCtx<E>,
     ) -> Result<(), SynthesisError> {
-        assert_eq!(ctx.width(), self.elements.len());
-
-            let mut new_state: Vec<num::Num<E>> = Vec::new();
-            let w = ctx.width();
-
-            for i in 0..w {
-                let row = ctx.mds_matrix_row(i);
-                let mut acc = num::Num::<E>::zero();
-                for j in 0..w {
-                    let mut r = self.elements[j].num();
-                    r.scale(row[j]);
-                    acc.add_assign(&r);
-                }
-                new_state.push(acc);
-            }
+        if ctx.width() != self.elements.len() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
 
-            // round ends here
-            let is_full_round = ctx.is_full_round();
-            let in_transition = ctx.in_transition();
-            ctx.round_end();
-
-            // // add round constants just after mds if
-            // // first full round has just ended
-            // // or in partial rounds expect the last one.
-            if in_transition == is_full_round {
-                // add round constants for elements in {1, t}
-                for i in 1..w {
-                    let round_constant = ctx.round_constant(Some(i));
-                    let mut constant_as_num = num::Num::<E>::zero();
-                    constant_as_num = constant_as_num.add_bool_with_coeff(
-                        CS::one(),
-                        &boolean::Boolean::Constant(true),
-                        round_constant,
-                    );
-                    new_state[i].add_assign(&constant_as_num);
-                }
+        let mut new_state: Vec<num::Num<E>> = Vec::new();
+        let w = ctx.width();
+
+        for i in 0..w {
+            let row = ctx.mds_matrix_row(i);
+            let mut acc = num::Num::<E>::zero();
+            for j in 0..w {
+                let mut r = self.elements[j].num()?;
+                r.scale(row[j]);
+                acc.add_assign(&r);
             }
+            new_state.push(acc);
+        }
 
-            for (s0, s1) in self.elements.iter_mut().zip(new_state) {
-                s0.update_with_num(s1);
+        // round ends here
+        let is_full_round = ctx.is_full_round();
+        let in_transition = ctx.in_transition();
+        ctx.round_end();
+
+        // // add round constants just after mds if
+        // // first full round has just ended
+        // // or in partial rounds expect the last one.
+        if in_transition == is_full_round {
+            // add round constants for elements in {1, t}
+            for i in 1..w {
+                let round_constant = ctx.round_constant(Some(i));
+                let mut constant_as_num = num::Num::<E>::zero();
+                constant_as_num = constant_as_num.add_bool_with_coeff(
+                    CS::one(),
+                    &boolean::Boolean::Constant(true),
+                    round_constant,
+                );
+                new_state[i].add_assign(&constant_as_num);
             }
-        
+        }
+
+        for (s0, s1) in self.elements.iter_mut().zip(new_state) {
+            s0.update_with_num(s1);
+        }
+
         Ok(())
     }
 }
@@ -319,7 +327,19 @@ where
     E: Engine,
 {
     pub fn new() -> Self {
-        Self { params: PoseidonParams::<E>::empty() }
+        Self {
+            params: Arc::new(PoseidonParams::<E>::empty()),
+            dense: false,
+        }
+    }
+
+    // round by round with the dense mds matrix, same outputs as new()
+    // with more constraints, kept to compare against
+    pub fn dense() -> Self {
+        Self {
+            params: Arc::new(PoseidonParams::<E>::empty()),
+            dense: true,
+        }
     }
 
     pub fn width(&self) -> usize {
@@ -332,12 +352,19 @@ where
         input: Vec<num::AllocatedNum<E>>,
     ) -> Result<num::AllocatedNum<E>, SynthesisError> {
         let num_inputs = input.len();
-        if num_inputs < 1 || num_inputs > 2  {
-            panic!("Invalid number of inputs");
+        if num_inputs < 1 || num_inputs >= MAX_WIDTH {
+            return Err(SynthesisError::Unsatisfiable);
         }
         let t = num_inputs + 1;
 
-        self.params = PoseidonParams::<E>::new(t);
+        self.params = PoseidonParams::<E>::cached(t).map_err(|_| SynthesisError::Unsatisfiable)?;
+
+        if !self.dense {
+            let mut state = vec![num::Num::zero()];
+            state.extend(input.into_iter().map(num::Num::from));
+            let state = permute(cs.namespace(|| "permutation"), &self.params, state)?;
+            return allocate(cs.namespace(|| "allocate result"), &state[0]);
+        }
 
         let mut elements: Vec<Element<E>> = vec![Element::new_from_num(num::Num::zero())];
 
@@ -352,21 +379,283 @@ where
         //     .collect();
         // elements.resize(self.width(), Element::new_from_num(num::Num::zero()));
 
-        let mut state = State::new(elements);
-        let mut ctx = RoundCtx::new(&self.params);
-        loop {
-            match ctx.round_type() {
-                RoundType::Exhausted => {
-                    break;
-                }
-                _ => {
-                    let round_number = ctx.round_number();
-                    state.sbox(cs.namespace(|| format!("sbox {}", round_number)), &mut ctx)?;
-                    state.mul_mds_matrix::<CS>(&mut ctx)?;
-                }
+        let mut state = permute_dense(cs.namespace(|| "permutation"), &self.params, elements)?;
+        state.first_allocated(cs.namespace(|| format!("allocate result")))
+    }
+}
+
+impl<E> HasherGadget<E> for PoseidonCircuit<E>
+where
+    E: Engine,
+{
+    type Native = PoseidonHasher<E>;
+
+    fn native(&self) -> PoseidonHasher<E> {
+        PoseidonHasher::new()
+    }
+
+    fn alloc<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: CS,
+        inputs: Vec<num::AllocatedNum<E>>,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError> {
+        PoseidonCircuit::alloc(self, cs, inputs)
+    }
+}
+
+fn permute_dense<E, CS>(
+    mut cs: CS,
+    params: &PoseidonParams<E>,
+    elements: Vec<Element<E>>,
+) -> Result<State<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let mut state = State::new(elements);
+    let mut ctx = RoundCtx::new(params);
+    loop {
+        match ctx.round_type() {
+            RoundType::Exhausted => {
+                break;
+            }
+            _ => {
+                let round_number = ctx.round_number();
+                state.sbox(cs.namespace(|| format!("sbox {}", round_number)), &mut ctx)?;
+                state.mul_mds_matrix::<CS>(&mut ctx)?;
             }
         }
-        state.first_allocated(cs.namespace(|| format!("allocate result")))
+    }
+    Ok(state)
+}
+
+// The sparse form of the permutation, see PoseidonParams::permute.
+// Elements stay linear combinations between rounds and only
+// the sbox outputs are allocated.
+fn permute<E, CS>(
+    mut cs: CS,
+    params: &PoseidonParams<E>,
+    mut state: Vec<num::Num<E>>,
+) -> Result<Vec<num::Num<E>>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    if state.len() != params.width() {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let t = params.width();
+    let a1 = params.full_round_half_len();
+    let a2 = a1 + params.partial_round_len();
+    let constants = params.sparse_round_constants();
+    let mds_matrix = params.mds_matrix();
+
+    for round in 0..params.total_rounds() {
+        let mut cs = cs.namespace(|| format!("round {}", round));
+        let c = &constants[round * t..(round + 1) * t];
+        if round < a1 || round >= a2 {
+            for (i, (s, c)) in state.iter_mut().zip(c.iter()).enumerate() {
+                *s = sbox(cs.namespace(|| format!("sbox {}", i)), s, *c)?.into();
+            }
+            state = if round == a1 - 1 {
+                mul_matrix(&state, params.pre_sparse_matrix())
+            } else {
+                mul_matrix(&state, &mds_matrix)
+            };
+        } else {
+            state[0] = sbox(cs.namespace(|| "sbox 0"), &state[0], c[0])?.into();
+            state = mul_sparse_matrix(&state, params.sparse_matrix(round - a1));
+        }
+    }
+    Ok(state)
+}
+
+// (x + c)^5 in three constraints, x is left as a linear combination
+pub(crate) fn sbox<E, CS>(
+    mut cs: CS,
+    x: &num::Num<E>,
+    c: E::Fr,
+) -> Result<num::AllocatedNum<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let mut x = x.clone();
+    x.add_assign(&constant::<E, CS>(c));
+    let x2 = num::AllocatedNum::alloc(cs.namespace(|| "x^2"), || {
+        let mut val = *x.get_value().get()?;
+        val.square();
+        Ok(val)
+    })?;
+    cs.enforce(
+        || "constraint x^2",
+        |_| x.lc(E::Fr::one()),
+        |_| x.lc(E::Fr::one()),
+        |lc| lc + x2.get_variable(),
+    );
+    let x4 = x2.square(cs.namespace(|| "x^4"))?;
+    let x5 = num::AllocatedNum::alloc(cs.namespace(|| "x^5"), || {
+        let mut val = *x4.get_value().get()?;
+        val.mul_assign(x.get_value().get()?);
+        Ok(val)
+    })?;
+    cs.enforce(
+        || "constraint x^5",
+        |_| x.lc(E::Fr::one()),
+        |lc| lc + x4.get_variable(),
+        |lc| lc + x5.get_variable(),
+    );
+    Ok(x5)
+}
+
+pub(crate) fn mul_matrix<E: Engine>(state: &[num::Num<E>], matrix: &[E::Fr]) -> Vec<num::Num<E>> {
+    matrix
+        .chunks(state.len())
+        .map(|row| {
+            let mut acc = num::Num::<E>::zero();
+            for (s, m) in state.iter().zip(row.iter()) {
+                let mut term = s.clone();
+                term.scale(*m);
+                acc.add_assign(&term);
+            }
+            acc
+        })
+        .collect()
+}
+
+// first row and first column, with ones on the rest of the diagonal
+fn mul_sparse_matrix<E: Engine>(state: &[num::Num<E>], sparse: &[E::Fr]) -> Vec<num::Num<E>> {
+    let (row, column) = sparse.split_at(state.len());
+    let mut new_state = mul_matrix(state, row);
+    for (s, m) in state[1..].iter().zip(column.iter()) {
+        let mut term = state[0].clone();
+        term.scale(*m);
+        term.add_assign(s);
+        new_state.push(term);
+    }
+    new_state
+}
+
+// a linear combination as a variable, in one constraint
+pub(crate) fn allocate<E, CS>(
+    cs: CS,
+    nu: &num::Num<E>,
+) -> Result<num::AllocatedNum<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    Element::new_from_num(nu.clone()).allocate(cs)
+}
+
+fn constant<E, CS>(value: E::Fr) -> num::Num<E>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    num::Num::zero().add_bool_with_coeff(CS::one(), &boolean::Boolean::Constant(true), value)
+}
+
+// In-circuit PoseidonSponge, outputs match the native ones
+#[derive(Clone)]
+pub struct PoseidonSpongeCircuit<E>
+where
+    E: Engine,
+{
+    params: Arc<PoseidonParams<E>>,
+    rate: usize,
+    domain: E::Fr,
+    state: Option<Vec<num::Num<E>>>,
+    position: usize,
+    permutations: usize,
+}
+
+impl<E> PoseidonSpongeCircuit<E>
+where
+    E: Engine,
+{
+    pub fn new(rate: usize, domain: E::Fr) -> Result<Self, RlnError> {
+        Ok(PoseidonSpongeCircuit {
+            params: PoseidonParams::<E>::cached(rate + 1)?,
+            rate,
+            domain,
+            state: None,
+            position: 0,
+            permutations: 0,
+        })
+    }
+
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    // capacity and rate, set up with the constant term of the constraint system
+    fn state<CS: ConstraintSystem<E>>(&mut self) -> &mut Vec<num::Num<E>> {
+        let (rate, domain) = (self.rate, self.domain);
+        self.state.get_or_insert_with(|| {
+            let mut state = vec![constant::<E, CS>(domain)];
+            state.resize(rate + 1, num::Num::zero());
+            state
+        })
+    }
+
+    fn permute<CS: ConstraintSystem<E>>(&mut self, mut cs: CS) -> Result<(), SynthesisError> {
+        let state = self.state::<CS>().clone();
+        let state = permute(
+            cs.namespace(|| format!("permutation {}", self.permutations)),
+            &self.params,
+            state,
+        )?;
+        self.state = Some(state);
+        self.permutations += 1;
+        Ok(())
+    }
+
+    pub fn absorb<CS: ConstraintSystem<E>>(
+        &mut self,
+        mut cs: CS,
+        inputs: &[num::AllocatedNum<E>],
+    ) -> Result<(), SynthesisError> {
+        for input in inputs.iter() {
+            if self.position == self.rate {
+                self.permute(&mut cs)?;
+                self.position = 0;
+            }
+            let position = self.position;
+            self.state::<CS>()[1 + position].add_assign(&num::Num::from(input.clone()));
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    // pads the absorbed elements and squeezes n outputs
+    pub fn squeeze<CS: ConstraintSystem<E>>(
+        mut self,
+        mut cs: CS,
+        n: usize,
+    ) -> Result<Vec<num::AllocatedNum<E>>, SynthesisError> {
+        if self.position == self.rate {
+            self.permute(&mut cs)?;
+            self.position = 0;
+        }
+        let position = self.position;
+        self.state::<CS>()[1 + position].add_assign(&constant::<E, CS>(E::Fr::one()));
+        self.permute(&mut cs)?;
+
+        let mut outputs = Vec::with_capacity(n);
+        while outputs.len() < n {
+            if !outputs.is_empty() && outputs.len() % self.rate == 0 {
+                self.permute(&mut cs)?;
+            }
+            let i = outputs.len();
+            let slot = 1 + i % self.rate;
+            let output = allocate(
+                cs.namespace(|| format!("output {}", i)),
+                &self.state::<CS>()[slot],
+            )?;
+            outputs.push(output);
+        }
+        Ok(outputs)
     }
 }
 
@@ -397,13 +686,112 @@ fn test_poseidon_canonical_circuit() {
         .alloc(cs.namespace(|| "hash alloc"), allocated_inputs)
         .unwrap();
     let result = res_allocated.get_value().unwrap();
-    let mut poseidon = PoseidonHasher::<Bn256>::new();
-    let expected = poseidon.hash(inputs);
+    let poseidon = PoseidonHasher::<Bn256>::new();
+    let expected = poseidon.hash(inputs).unwrap();
 
     assert_eq!(result, expected);
     assert!(cs.is_satisfied());
-    println!(
-        "number of constraints for {}",
-        cs.num_constraints()
-    );
+    println!("number of constraints for {}", cs.num_constraints());
+}
+
+#[test]
+fn test_poseidon_wide_circuit() {
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::PrimeField;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    for n in [4, 7, MAX_WIDTH - 1].iter().cloned() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let inputs: Vec<Fr> = (0..n)
+            .map(|i| Fr::from_str(&(i * 7 + 1).to_string()).unwrap())
+            .collect();
+        let allocated_inputs = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                num::AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e))
+                    .unwrap()
+            })
+            .collect();
+
+        let mut circuit = PoseidonCircuit::<Bn256>::new();
+        let result = circuit
+            .alloc(cs.namespace(|| "hash alloc"), allocated_inputs)
+            .unwrap();
+        let expected = PoseidonHasher::<Bn256>::new().hash(inputs).unwrap();
+        assert_eq!(result.get_value().unwrap(), expected);
+        assert!(cs.is_satisfied());
+    }
+
+    let mut cs = TestConstraintSystem::<Bn256>::new();
+    assert!(PoseidonCircuit::<Bn256>::new()
+        .alloc(cs.namespace(|| "no inputs"), vec![])
+        .is_err());
+}
+
+#[test]
+fn test_poseidon_bench() {
+    use crate::circuit::bench::run_poseidon_bench;
+    use sapling_crypto::bellman::pairing::bls12_381::Bls12;
+    use sapling_crypto::bellman::pairing::bn256::Bn256;
+
+    // constraints of the round by round and of the sparse permutation,
+    // the sparse one takes 3 per sbox, 8 * t + rp sboxes, and 1 for the output
+    let results = vec![
+        (run_poseidon_bench::<Bn256>(3, 2000), 323, 244),
+        (run_poseidon_bench::<Bn256>(5, 2000), 397, 301),
+        (run_poseidon_bench::<Bn256>(MAX_WIDTH, 500), 801, 613),
+        (run_poseidon_bench::<Bls12>(3, 2000), 323, 244),
+    ];
+    for (result, dense_constraints, sparse_constraints) in results.iter() {
+        println!("width: {}", result.width);
+        println!("dense permutations:\t{}", result.dense_time);
+        println!("sparse permutations:\t{}", result.sparse_time);
+        println!("dense constraints:\t{}", result.dense_constraints);
+        println!("sparse constraints:\t{}", result.sparse_constraints);
+        assert_eq!(result.dense_constraints, *dense_constraints);
+        assert_eq!(result.sparse_constraints, *sparse_constraints);
+    }
+}
+
+#[test]
+fn test_poseidon_sponge_circuit() {
+    use crate::poseidon::PoseidonSponge;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::PrimeField;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    let domain = Fr::from_str("3").unwrap();
+    for (rate, n) in [(2, 0), (2, 1), (2, 2), (2, 7), (4, 9), (16, 16)]
+        .iter()
+        .cloned()
+    {
+        let inputs: Vec<Fr> = (0..n)
+            .map(|i| Fr::from_str(&(i * 5 + 2).to_string()).unwrap())
+            .collect();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let allocated_inputs: Vec<num::AllocatedNum<Bn256>> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                num::AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e))
+                    .unwrap()
+            })
+            .collect();
+
+        let mut sponge = PoseidonSpongeCircuit::<Bn256>::new(rate, domain).unwrap();
+        let (first, rest) = allocated_inputs.split_at(n / 2);
+        sponge.absorb(cs.namespace(|| "first"), first).unwrap();
+        sponge.absorb(cs.namespace(|| "rest"), rest).unwrap();
+        let outputs = sponge
+            .squeeze(cs.namespace(|| "squeeze"), 2 * rate + 1)
+            .unwrap();
+
+        let mut native = PoseidonSponge::<Bn256>::new(rate, domain).unwrap();
+        native.absorb(&inputs);
+        let expected = native.squeeze(2 * rate + 1);
+        let outputs: Vec<Fr> = outputs.iter().map(|o| o.get_value().unwrap()).collect();
+        assert_eq!(outputs, expected);
+        assert!(cs.is_satisfied());
+    }
 }
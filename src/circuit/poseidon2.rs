@@ -0,0 +1,169 @@
+use crate::circuit::hasher::HasherGadget;
+use crate::circuit::poseidon::{allocate, mul_matrix, sbox};
+use crate::poseidon2::{Poseidon2, Poseidon2Params, MAX_WIDTH};
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
+use sapling_crypto::circuit::num;
+use std::marker::PhantomData;
+
+// In-circuit Poseidon2, the linear layers only build linear combinations
+// so the constraints are the sboxes and the allocation of the digest.
+#[derive(Clone)]
+pub struct Poseidon2Circuit<E>
+where
+    E: Engine,
+{
+    _engine: PhantomData<E>,
+}
+
+impl<E> Default for Poseidon2Circuit<E>
+where
+    E: Engine,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Poseidon2Circuit<E>
+where
+    E: Engine,
+{
+    pub fn new() -> Self {
+        Poseidon2Circuit {
+            _engine: PhantomData,
+        }
+    }
+
+    pub fn alloc<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        input: Vec<num::AllocatedNum<E>>,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError> {
+        let num_inputs = input.len();
+        if !(1..MAX_WIDTH).contains(&num_inputs) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let t = num_inputs + 1;
+        let params = Poseidon2Params::<E>::cached(t).map_err(|_| SynthesisError::Unsatisfiable)?;
+
+        let mut state = vec![num::Num::zero()];
+        state.extend(input.into_iter().map(num::Num::from));
+        let state = permute(cs.namespace(|| "permutation"), &params, state)?;
+        allocate(cs.namespace(|| "allocate result"), &state[0])
+    }
+}
+
+impl<E> HasherGadget<E> for Poseidon2Circuit<E>
+where
+    E: Engine,
+{
+    type Native = Poseidon2<E>;
+
+    fn native(&self) -> Poseidon2<E> {
+        Poseidon2::new()
+    }
+
+    fn alloc<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: CS,
+        inputs: Vec<num::AllocatedNum<E>>,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError> {
+        Poseidon2Circuit::alloc(self, cs, inputs)
+    }
+}
+
+fn permute<E, CS>(
+    mut cs: CS,
+    params: &Poseidon2Params<E>,
+    state: Vec<num::Num<E>>,
+) -> Result<Vec<num::Num<E>>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    let t = params.width();
+    let a1 = params.full_round_half_len();
+    let a2 = a1 + params.partial_round_len();
+    let external_matrix = params.external_matrix();
+
+    let mut state = mul_matrix(&state, &external_matrix);
+    for round in 0..params.total_rounds() {
+        let mut cs = cs.namespace(|| format!("round {}", round));
+        let c = &params.round_constants()[round * t..(round + 1) * t];
+        if round < a1 || round >= a2 {
+            for (i, (s, c)) in state.iter_mut().zip(c.iter()).enumerate() {
+                *s = sbox(cs.namespace(|| format!("sbox {}", i)), s, *c)?.into();
+            }
+            state = mul_matrix(&state, &external_matrix);
+        } else {
+            state[0] = sbox(cs.namespace(|| "sbox 0"), &state[0], c[0])?.into();
+            state = mul_internal(&state, params.internal_diagonal());
+        }
+    }
+    Ok(state)
+}
+
+fn mul_internal<E: Engine>(state: &[num::Num<E>], diagonal: &[E::Fr]) -> Vec<num::Num<E>> {
+    let mut sum = num::Num::<E>::zero();
+    for s in state.iter() {
+        sum.add_assign(s);
+    }
+    state
+        .iter()
+        .zip(diagonal.iter())
+        .map(|(s, d)| {
+            let mut s = s.clone();
+            s.scale(*d);
+            s.add_assign(&sum);
+            s
+        })
+        .collect()
+}
+
+#[test]
+fn test_poseidon2_circuit() {
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::PrimeField;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    for n in 1..MAX_WIDTH {
+        let inputs: Vec<Fr> = (0..n)
+            .map(|i| Fr::from_str(&(i * 3 + 1).to_string()).unwrap())
+            .collect();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let allocated_inputs: Vec<num::AllocatedNum<Bn256>> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                num::AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e))
+                    .unwrap()
+            })
+            .collect();
+
+        let result = Poseidon2Circuit::<Bn256>::new()
+            .alloc(cs.namespace(|| "poseidon2"), allocated_inputs.clone())
+            .unwrap();
+        let expected = Poseidon2::<Bn256>::new().hash(inputs.clone()).unwrap();
+        assert_eq!(result.get_value().unwrap(), expected);
+        assert!(cs.is_satisfied());
+        let constraints = cs.num_constraints();
+
+        PoseidonCircuit::<Bn256>::new()
+            .alloc(cs.namespace(|| "poseidon"), allocated_inputs)
+            .unwrap();
+        assert!(cs.is_satisfied());
+        println!("width: {}", n + 1);
+        println!("poseidon2 constraints:\t{}", constraints);
+        println!(
+            "poseidon constraints:\t{}",
+            cs.num_constraints() - constraints
+        );
+    }
+
+    let mut cs = TestConstraintSystem::<Bn256>::new();
+    assert!(Poseidon2Circuit::<Bn256>::new()
+        .alloc(cs.namespace(|| "no inputs"), vec![])
+        .is_err());
+}
@@ -1,6 +1,12 @@
-use crate::circuit::polynomial::allocate_add_with_coeff;
+use crate::circuit::gadgets::{merkle_membership, nullifier, share_evaluation};
+use crate::circuit::hasher::HasherGadget;
 use crate::circuit::poseidon::PoseidonCircuit;
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::identity::Identity;
+use crate::merkle::{MembershipTree, MerkleTree};
 use crate::poseidon::{Poseidon as PoseidonHasher, PoseidonParams};
+use crate::utils::read_fr;
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{Circuit, ConstraintSystem, SynthesisError, Variable};
@@ -24,10 +30,14 @@ where
     pub share_x: Option<E::Fr>,
     pub share_y: Option<E::Fr>,
 
-    // epoch is the external nullifier
-    // we derive the line equation and the nullifier from epoch
+    // epoch together with rln_identifier makes the external nullifier
+    // we derive the line equation and the nullifier from
     pub epoch: Option<E::Fr>,
 
+    // rln_identifier tags the application,
+    // so that one identity gives unlinkable nullifiers across applications
+    pub rln_identifier: Option<E::Fr>,
+
     // nullifier
     pub nullifier: Option<E::Fr>,
 
@@ -49,14 +59,24 @@ impl<E> RLNInputs<E>
 where
     E: Engine,
 {
-    pub fn public_inputs(&self) -> Vec<E::Fr> {
-        vec![
-            self.root.unwrap(),
-            self.epoch.unwrap(),
-            self.share_x.unwrap(),
-            self.share_y.unwrap(),
-            self.nullifier.unwrap(),
-        ]
+    pub fn public_inputs(&self) -> Result<Vec<E::Fr>, RlnError> {
+        Ok(vec![
+            value(self.root)?,
+            value(self.epoch)?,
+            value(self.share_x)?,
+            value(self.share_y)?,
+            value(self.nullifier)?,
+            value(self.rln_identifier)?,
+        ])
+    }
+
+    // external nullifier H(epoch, rln_identifier)
+    pub fn external_nullifier<H: Hasher<E>>(
+        hasher: &H,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+    ) -> Result<E::Fr, RlnError> {
+        hasher.hash(vec![epoch, rln_identifier])
     }
 
     pub fn merkle_depth(&self) -> usize {
@@ -68,6 +88,7 @@ where
             share_x: None,
             share_y: None,
             epoch: None,
+            rln_identifier: None,
             nullifier: None,
             root: None,
             id_key: None,
@@ -75,34 +96,93 @@ where
         }
     }
 
-    pub fn read<R: Read>(mut reader: R) -> io::Result<RLNInputs<E>> {
-        let mut buf = <E::Fr as PrimeField>::Repr::default();
-
-        buf.read_le(&mut reader)?;
-        let share_x =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-            
-        buf.read_le(&mut reader)?;
-        let share_y =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let epoch =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let nullifier =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let root =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let id_key =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // builds the full witness of a member signalling in an epoch,
+    // share_x is expected to be the hash of the signal. Hashes are taken
+    // with the hasher of the tree, which the circuit hasher should match.
+    pub fn from_identity<T: MembershipTree<E>>(
+        id_key: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        share_x: E::Fr,
+    ) -> Result<RLNInputs<E>, RlnError> {
+        let hasher = membership_tree.hasher();
+        let auth_path = membership_tree.witness(leaf_index)?;
+
+        // a_1 = hash(a_0, hash(epoch, rln_identifier))
+        let a_0 = id_key;
+        let external_nullifier = Self::external_nullifier(hasher, epoch, rln_identifier)?;
+        let a_1 = hasher.hash(vec![a_0, external_nullifier])?;
+
+        // share_y = a_0 + a_1 * share_x
+        let mut share_y = a_1;
+        share_y.mul_assign(&share_x);
+        share_y.add_assign(&a_0);
+
+        let nullifier = hasher.hash(vec![a_1])?;
+
+        Ok(RLNInputs::<E> {
+            share_x: Some(share_x),
+            share_y: Some(share_y),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(nullifier),
+            root: Some(membership_tree.root()),
+            id_key: Some(id_key),
+            auth_path: auth_path.into_iter().map(Some).collect(),
+        })
+    }
+
+    // checks natively that the private inputs satisfy the circuit hashing with
+    // the native counterpart of its hasher, so that a bad witness is reported
+    // instead of yielding an invalid proof
+    pub fn check_witness<H: Hasher<E>>(&self, hasher: &H) -> Result<(), RlnError> {
+        let id_key = value(self.id_key)?;
+
+        let mut acc = hasher.hash(vec![id_key])?;
+        for e in self.auth_path.iter() {
+            let (path_element, position) = value(*e)?;
+            acc = if position {
+                hasher.hash(vec![acc, path_element])?
+            } else {
+                hasher.hash(vec![path_element, acc])?
+            };
+        }
+        if acc != value(self.root)? {
+            return Err(RlnError::UnsatisfiedWitness("membership"));
+        }
+
+        let external_nullifier =
+            Self::external_nullifier(hasher, value(self.epoch)?, value(self.rln_identifier)?)?;
+        let a_1 = hasher.hash(vec![id_key, external_nullifier])?;
+        let mut share_y = a_1;
+        share_y.mul_assign(&value(self.share_x)?);
+        share_y.add_assign(&id_key);
+        if share_y != value(self.share_y)? {
+            return Err(RlnError::UnsatisfiedWitness("line equation"));
+        }
+
+        if hasher.hash(vec![a_1])? != value(self.nullifier)? {
+            return Err(RlnError::UnsatisfiedWitness("nullifier"));
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<RLNInputs<E>, RlnError> {
+        let share_x = read_fr::<_, E>(&mut reader)?;
+        let share_y = read_fr::<_, E>(&mut reader)?;
+        let epoch = read_fr::<_, E>(&mut reader)?;
+        let rln_identifier = read_fr::<_, E>(&mut reader)?;
+        let nullifier = read_fr::<_, E>(&mut reader)?;
+        let root = read_fr::<_, E>(&mut reader)?;
+        let id_key = read_fr::<_, E>(&mut reader)?;
         let auth_path = Self::decode_auth_path(&mut reader)?;
         Ok(RLNInputs {
             share_x: Some(share_x),
             share_y: Some(share_y),
             epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
             nullifier: Some(nullifier),
             root: Some(root),
             id_key: Some(id_key),
@@ -110,99 +190,62 @@ where
         })
     }
 
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        self.share_x
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        self.share_y
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        self.epoch
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        self.nullifier
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        self.root
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        self.id_key
-            .unwrap()
-            .into_repr()
-            .write_le(&mut writer)
-            .unwrap();
-        Self::encode_auth_path(&mut writer, self.auth_path.clone()).unwrap();
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        value(self.share_x)?.into_repr().write_le(&mut writer)?;
+        value(self.share_y)?.into_repr().write_le(&mut writer)?;
+        value(self.epoch)?.into_repr().write_le(&mut writer)?;
+        value(self.rln_identifier)?.into_repr().write_le(&mut writer)?;
+        value(self.nullifier)?.into_repr().write_le(&mut writer)?;
+        value(self.root)?.into_repr().write_le(&mut writer)?;
+        value(self.id_key)?.into_repr().write_le(&mut writer)?;
+        Self::encode_auth_path(&mut writer, self.auth_path.clone())?;
         Ok(())
     }
 
-    pub fn read_public_inputs<R: Read>(mut reader: R) -> io::Result<Vec<E::Fr>> {
-        let mut buf = <E::Fr as PrimeField>::Repr::default();
-        buf.read_le(&mut reader)?;
-        let root =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let epoch =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let share_x =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let share_y =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        buf.read_le(&mut reader)?;
-        let nullifier =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        Ok(vec![root, epoch, share_x, share_y, nullifier])
+    pub fn read_public_inputs<R: Read>(mut reader: R) -> Result<Vec<E::Fr>, RlnError> {
+        let root = read_fr::<_, E>(&mut reader)?;
+        let epoch = read_fr::<_, E>(&mut reader)?;
+        let share_x = read_fr::<_, E>(&mut reader)?;
+        let share_y = read_fr::<_, E>(&mut reader)?;
+        let nullifier = read_fr::<_, E>(&mut reader)?;
+        let rln_identifier = read_fr::<_, E>(&mut reader)?;
+        Ok(vec![root, epoch, share_x, share_y, nullifier, rln_identifier])
     }
 
-    pub fn write_public_inputs<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        self.root.unwrap().into_repr().write_le(&mut writer)?;
-        self.epoch.unwrap().into_repr().write_le(&mut writer)?;
-        self.share_x.unwrap().into_repr().write_le(&mut writer)?;
-        self.share_y.unwrap().into_repr().write_le(&mut writer)?;
-        self.nullifier.unwrap().into_repr().write_le(&mut writer)?;
+    pub fn write_public_inputs<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        for input in self.public_inputs()?.iter() {
+            input.into_repr().write_le(&mut writer)?;
+        }
         Ok(())
     }
 
     pub fn encode_auth_path<W: Write>(
         mut writer: W,
         auth_path: Vec<Option<(E::Fr, bool)>>,
-    ) -> io::Result<()> {
+    ) -> Result<(), RlnError> {
         let path_len = auth_path.len() as u8;
-        writer.write(&[path_len])?;
+        writer.write_all(&[path_len])?;
         for el in auth_path.iter() {
-            let c = el.unwrap();
+            let c = value(*el)?;
             if c.1 {
-                writer.write(&[1])?;
+                writer.write_all(&[1])?;
             } else {
-                writer.write(&[0])?;
+                writer.write_all(&[0])?;
             }
-            c.0.into_repr().write_le(&mut writer).unwrap();
+            c.0.into_repr().write_le(&mut writer)?;
         }
         Ok(())
     }
 
-    pub fn decode_auth_path<R: Read>(mut reader: R) -> io::Result<Vec<Option<(E::Fr, bool)>>> {
+    pub fn decode_auth_path<R: Read>(
+        mut reader: R,
+    ) -> Result<Vec<Option<(E::Fr, bool)>>, RlnError> {
         let mut byte_buf = vec![0u8; 1];
-        let mut el_buf = <E::Fr as PrimeField>::Repr::default();
         let mut auth_path: Vec<Option<(E::Fr, bool)>> = vec![];
         reader.read_exact(&mut byte_buf)?;
         let path_len = byte_buf[0];
         if path_len < 2 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "invalid path length",
-            ));
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid path length").into());
         }
         for _ in 0..path_len {
             reader.read_exact(&mut byte_buf)?;
@@ -213,161 +256,287 @@ where
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidInput,
                         "invalid path direction",
-                    ))
+                    )
+                    .into())
                 }
             };
-            el_buf.read_le(&mut reader)?;
-            let node = E::Fr::from_repr(el_buf)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let node = read_fr::<_, E>(&mut reader)?;
             auth_path.push(Some((node, path_dir)));
         }
         Ok(auth_path)
     }
 }
 
+pub(crate) fn value<T>(v: Option<T>) -> Result<T, RlnError> {
+    v.ok_or(RlnError::Synthesis(SynthesisError::AssignmentMissing))
+}
+
 #[derive(Clone)]
-pub struct RLNCircuit<E>
+pub struct RLNCircuit<E, H = PoseidonCircuit<E>>
 where
     E: Engine,
+    H: HasherGadget<E>,
 {
     pub inputs: RLNInputs<E>,
-    pub hasher: PoseidonCircuit<E>
+    // takes every hash of the circuit
+    pub hasher: H,
 }
 
-impl<E> Circuit<E> for RLNCircuit<E>
+impl<E, H> Circuit<E> for RLNCircuit<E, H>
 where
     E: Engine,
+    H: HasherGadget<E>,
 {
     fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
-        // 1. Part
-        // Membership constraints
-        // root == merkle_proof(auth_path, preimage_of_leaf)
-
-        let mut hasher = self.hasher.clone();
-
-        let root = num::AllocatedNum::alloc(cs.namespace(|| "root"), || {
-            let value = self.inputs.root.clone();
-            Ok(*value.get()?)
-        })?;
-        root.inputize(cs.namespace(|| "root is public"))?;
-
         let preimage = num::AllocatedNum::alloc(cs.namespace(|| "preimage"), || {
             let value = self.inputs.id_key;
             Ok(*value.get()?)
         })?;
+        let share_x = num::AllocatedNum::alloc(cs.namespace(|| "share x"), || {
+            let value = self.inputs.share_x;
+            Ok(*value.get()?)
+        })?;
+        synthesize_rln(cs, &self.inputs, self.hasher, preimage, share_x)
+    }
+}
 
-        // identity is a leaf of membership tree
-
-        let identity = hasher
-            .alloc(cs.namespace(|| "identity"), vec![preimage.clone()])?;
-
-        // accumulator up to the root
-
-        let mut acc = identity.clone();
-
-        // ascend the tree
+// Semaphore style identity
+// the leaf is H(H(identity_nullifier, identity_trapdoor)),
+// so the id_key of the inputs is H(identity_nullifier, identity_trapdoor)
+// and the public inputs are the ones of RLNCircuit
+#[derive(Clone)]
+pub struct RLNIdentityCircuit<E, H = PoseidonCircuit<E>>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    pub inputs: RLNInputs<E>,
+    pub identity_nullifier: Option<E::Fr>,
+    pub identity_trapdoor: Option<E::Fr>,
+    // takes every hash of the circuit
+    pub hasher: H,
+}
 
-        let auth_path_witness = self.inputs.auth_path.clone();
-        for (i, e) in auth_path_witness.into_iter().enumerate() {
-            let cs = &mut cs.namespace(|| format!("auth path {}", i));
-            let position = boolean::Boolean::from(boolean::AllocatedBit::alloc(
-                cs.namespace(|| "position bit"),
-                e.map(|e| e.1),
-            )?);
-            let path_element =
-                num::AllocatedNum::alloc(cs.namespace(|| "path element"), || Ok(e.get()?.0))?;
+impl<E> RLNIdentityCircuit<E>
+where
+    E: Engine,
+{
+    pub fn empty(merkle_depth: usize) -> RLNIdentityCircuit<E> {
+        Self::empty_with_hasher(merkle_depth, PoseidonCircuit::new())
+    }
 
-            let (xr, xl) = num::AllocatedNum::conditionally_reverse(
-                cs.namespace(|| "conditional reversal of preimage"),
-                &acc,
-                &path_element,
-                &position,
-            )?;
+    pub fn from_identity<T: MembershipTree<E, Hasher = PoseidonHasher<E>>>(
+        identity: &Identity<E>,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        share_x: E::Fr,
+    ) -> Result<RLNIdentityCircuit<E>, RlnError> {
+        Self::from_identity_with_hasher(
+            identity,
+            membership_tree,
+            leaf_index,
+            epoch,
+            rln_identifier,
+            share_x,
+            PoseidonCircuit::new(),
+        )
+    }
+}
 
-            acc = hasher
-                .alloc(cs.namespace(|| "hash couple"), vec![xl, xr])?;
+impl<E, H> RLNIdentityCircuit<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    pub fn empty_with_hasher(merkle_depth: usize, hasher: H) -> RLNIdentityCircuit<E, H> {
+        RLNIdentityCircuit {
+            inputs: RLNInputs::empty(merkle_depth),
+            identity_nullifier: None,
+            identity_trapdoor: None,
+            hasher,
         }
+    }
 
-        // see if it is a member
+    // circuit of a member signalling in an epoch with a semaphore identity,
+    // the identity and the tree hash with the native counterpart of the hasher
+    pub fn from_identity_with_hasher<T: MembershipTree<E, Hasher = H::Native>>(
+        identity: &Identity<E, H::Native>,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        share_x: E::Fr,
+        hasher: H,
+    ) -> Result<RLNIdentityCircuit<E, H>, RlnError> {
+        let inputs = RLNInputs::from_identity(
+            identity.secret()?,
+            membership_tree,
+            leaf_index,
+            epoch,
+            rln_identifier,
+            share_x,
+        )?;
+        Ok(RLNIdentityCircuit {
+            inputs,
+            identity_nullifier: Some(identity.nullifier()),
+            identity_trapdoor: Some(identity.trapdoor()),
+            hasher,
+        })
+    }
 
-        cs.enforce(
-            || "enforce membership",
-            |lc| lc + acc.get_variable(),
-            |lc| lc + CS::one(),
-            |lc| lc + root.get_variable(),
+    pub fn check_witness(&self) -> Result<(), RlnError> {
+        let hasher = self.hasher.native();
+        let identity = Identity::new_with_hasher(
+            value(self.identity_trapdoor)?,
+            value(self.identity_nullifier)?,
+            hasher.clone(),
         );
+        if Some(identity.secret()?) != self.inputs.id_key {
+            return Err(RlnError::UnsatisfiedWitness("identity secret"));
+        }
+        self.inputs.check_witness(&hasher)
+    }
+}
 
-        // 2. Part
-        // Line Equation Constaints
-        // a_1 = hash(a_0, epoch)
-        // share_y == a_0 + a_1 * share_x
+impl<E, H> Circuit<E> for RLNIdentityCircuit<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut hasher = self.hasher.clone();
 
-        let epoch = num::AllocatedNum::alloc(cs.namespace(|| "epoch"), || {
-            let value = self.inputs.epoch.clone();
+        let identity_nullifier =
+            num::AllocatedNum::alloc(cs.namespace(|| "identity nullifier"), || {
+                let value = self.identity_nullifier;
+                Ok(*value.get()?)
+            })?;
+        let identity_trapdoor =
+            num::AllocatedNum::alloc(cs.namespace(|| "identity trapdoor"), || {
+                let value = self.identity_trapdoor;
+                Ok(*value.get()?)
+            })?;
+
+        // secret = hash(identity_nullifier, identity_trapdoor)
+
+        let preimage = hasher.alloc(
+            cs.namespace(|| "identity secret"),
+            vec![identity_nullifier, identity_trapdoor],
+        )?;
+        let share_x = num::AllocatedNum::alloc(cs.namespace(|| "share x"), || {
+            let value = self.inputs.share_x;
             Ok(*value.get()?)
         })?;
-        epoch.inputize(cs.namespace(|| "epoch is public"))?;
+        synthesize_rln(cs, &self.inputs, hasher, preimage, share_x)
+    }
+}
+
+// constraints of RLNCircuit over an allocated id_key and share_x,
+// share_x is made public in its place among the public inputs
+pub(crate) fn synthesize_rln<E, CS, H>(
+    cs: &mut CS,
+    inputs: &RLNInputs<E>,
+    mut hasher: H,
+    preimage: num::AllocatedNum<E>,
+    share_x: num::AllocatedNum<E>,
+) -> Result<(), SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+    H: HasherGadget<E>,
+{
+    // 1. Part
+    // Membership constraints
+    // root == merkle_proof(auth_path, preimage_of_leaf)
 
-        let a_0 = preimage.clone();
+    // allocated early, made public after the nullifier
+    // to keep the order of public inputs
+    let rln_identifier = num::AllocatedNum::alloc(cs.namespace(|| "rln identifier"), || {
+        let value = inputs.rln_identifier;
+        Ok(*value.get()?)
+    })?;
 
-        // a_1 == h(a_0, epoch)
+    let root = num::AllocatedNum::alloc(cs.namespace(|| "root"), || {
+        let value = inputs.root.clone();
+        Ok(*value.get()?)
+    })?;
+    root.inputize(cs.namespace(|| "root is public"))?;
 
-        let a_1 = hasher
-            .alloc(cs.namespace(|| "a_1"), vec![a_0.clone(), epoch])?;
+    // identity is a leaf of membership tree
 
-        let share_x = num::AllocatedNum::alloc(cs.namespace(|| "share x"), || {
-            let value = self.inputs.share_x.clone();
-            Ok(*value.get()?)
-        })?;
-        share_x.inputize(cs.namespace(|| "share x is public"))?;
+    let identity = hasher.alloc(cs.namespace(|| "identity"), vec![preimage.clone()])?;
 
-        // constaint the evaluation the line equation
+    merkle_membership(
+        cs.namespace(|| "membership"),
+        &mut hasher,
+        &identity,
+        &inputs.auth_path,
+        &root,
+    )?;
 
-        let eval = allocate_add_with_coeff(cs.namespace(|| "eval"), &a_1, &share_x, &a_0)?;
+    // 2. Part
+    // Line Equation Constaints
+    // a_1 = hash(a_0, hash(epoch, rln_identifier))
+    // share_y == a_0 + a_1 * share_x
 
-        let share_y = num::AllocatedNum::alloc(cs.namespace(|| "share y"), || {
-            let value = self.inputs.share_y.clone();
-            Ok(*value.get()?)
-        })?;
-        share_y.inputize(cs.namespace(|| "share y is public"))?;
+    let epoch = num::AllocatedNum::alloc(cs.namespace(|| "epoch"), || {
+        let value = inputs.epoch.clone();
+        Ok(*value.get()?)
+    })?;
+    epoch.inputize(cs.namespace(|| "epoch is public"))?;
 
-        // see if share satisfies the line equation
+    let a_0 = preimage.clone();
 
-        cs.enforce(
-            || "enforce lookup",
-            |lc| lc + share_y.get_variable(),
-            |lc| lc + CS::one(),
-            |lc| lc + eval.get_variable(),
-        );
+    // external nullifier is scoped to the application
 
-        // 3. Part
-        // Nullifier constraints
+    let external_nullifier = hasher.alloc(
+        cs.namespace(|| "external nullifier"),
+        vec![epoch, rln_identifier.clone()],
+    )?;
 
-        // hashing secret twice with epoch ingredient
-        // a_1 == hash(a_0, epoch) is already constrained
+    // a_1 == h(a_0, external_nullifier)
 
-        // nullifier == hash(a_1)
+    let a_1 = hasher
+        .alloc(cs.namespace(|| "a_1"), vec![a_0.clone(), external_nullifier])?;
 
-        let nullifier_calculated = hasher
-            .alloc(cs.namespace(|| "calculated nullifier"), vec![a_1.clone()])?;
+    share_x.inputize(cs.namespace(|| "share x is public"))?;
 
-        let nullifier = num::AllocatedNum::alloc(cs.namespace(|| "nullifier"), || {
-            let value = self.inputs.nullifier.clone();
-            Ok(*value.get()?)
-        })?;
-        nullifier.inputize(cs.namespace(|| "nullifier is public"))?;
+    let share_y = num::AllocatedNum::alloc(cs.namespace(|| "share y"), || {
+        let value = inputs.share_y.clone();
+        Ok(*value.get()?)
+    })?;
+    share_y.inputize(cs.namespace(|| "share y is public"))?;
 
-        // check if correct nullifier supplied
+    // see if share satisfies the line equation
 
-        cs.enforce(
-            || "enforce nullifier",
-            |lc| lc + nullifier_calculated.get_variable(),
-            |lc| lc + CS::one(),
-            |lc| lc + nullifier.get_variable(),
-        );
+    share_evaluation(cs.namespace(|| "share"), &a_0, &a_1, &share_x, &share_y)?;
 
-        Ok(())
-    }
+    // 3. Part
+    // Nullifier constraints
+
+    // hashing secret twice with epoch ingredient
+    // a_1 == hash(a_0, epoch) is already constrained
+
+    // nullifier == hash(a_1)
+
+    let nullifier_value = num::AllocatedNum::alloc(cs.namespace(|| "nullifier"), || {
+        let value = inputs.nullifier.clone();
+        Ok(*value.get()?)
+    })?;
+    nullifier_value.inputize(cs.namespace(|| "nullifier is public"))?;
+
+    rln_identifier.inputize(cs.namespace(|| "rln identifier is public"))?;
+
+    // check if correct nullifier supplied
+
+    nullifier(
+        cs.namespace(|| "nullifier check"),
+        &mut hasher,
+        &a_1,
+        &nullifier_value,
+    )
 }
 
 #[cfg(test)]
@@ -418,6 +587,93 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_rln_bls() {
+        let rln_test = bench::RLNTest::<Bls12>::new(3);
+        let num_constraints = rln_test.synthesize();
+        let result = rln_test.run_prover_bench();
+        println!("bls12-381, merkle depth: 3");
+        println!("number of constatins:\t{}", num_constraints);
+        println!("prover time:\t{}", result.prover_time);
+    }
+
+    #[test]
+    fn test_rln_poseidon2() {
+        use crate::circuit::poseidon::PoseidonCircuit;
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        let poseidon = bench::run_rln_hasher_bench::<Bn256, _>(20, PoseidonCircuit::new());
+        let poseidon2 = bench::run_rln_hasher_bench::<Bn256, _>(20, Poseidon2Circuit::new());
+        println!("bn256, merkle depth: 20");
+        println!("poseidon constraints:\t{}", poseidon.constraints);
+        println!("poseidon prover time:\t{}", poseidon.prover_time);
+        println!("poseidon2 constraints:\t{}", poseidon2.constraints);
+        println!("poseidon2 prover time:\t{}", poseidon2.prover_time);
+        assert!(poseidon2.constraints <= poseidon.constraints);
+    }
+
+    #[test]
+    fn test_seeded_proof_is_deterministic() {
+        use crate::public::RLN;
+        use rand::{SeedableRng, XorShiftRng};
+        use sapling_crypto::bellman::pairing::bn256::Bn256;
+        let merkle_depth = 3;
+        let rln_test = bench::RLNTest::<Bn256>::new(merkle_depth);
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        rln_test.valid_inputs().write(&mut raw_inputs).unwrap();
+        let rln = RLN::<Bn256>::new(merkle_depth, bench::RLNTest::<Bn256>::rln_identifier()).unwrap();
+        let prove = |seed: [u32; 4]| {
+            let mut rng = XorShiftRng::from_seed(seed);
+            let mut proof: Vec<u8> = Vec::new();
+            rln.generate_proof_with_rng(raw_inputs.as_slice(), &mut proof, &mut rng)
+                .unwrap();
+            proof
+        };
+        assert_eq!(prove([1, 2, 3, 4]), prove([1, 2, 3, 4]));
+        assert_ne!(prove([1, 2, 3, 4]), prove([5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn test_invalid_inputs_are_errors() {
+        use crate::error::RlnError;
+        use crate::public::RLN;
+        use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+        use sapling_crypto::bellman::pairing::ff::Field;
+        let merkle_depth = 3;
+        let rln_test = bench::RLNTest::<Bn256>::new(merkle_depth);
+        let rln = RLN::<Bn256>::new(merkle_depth + 1, bench::RLNTest::<Bn256>::rln_identifier()).unwrap();
+        let mut inputs = rln_test.valid_inputs();
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        inputs.write(&mut raw_inputs).unwrap();
+
+        let mut proof: Vec<u8> = Vec::new();
+        match rln.generate_proof(raw_inputs.as_slice(), &mut proof) {
+            Err(RlnError::DepthMismatch { expected, actual }) => {
+                assert_eq!(expected, merkle_depth + 1);
+                assert_eq!(actual, merkle_depth);
+            }
+            _ => panic!("expected depth mismatch"),
+        }
+
+        match rln.generate_proof(&raw_inputs[..raw_inputs.len() - 1], &mut proof) {
+            Err(RlnError::Serialization(_)) => (),
+            _ => panic!("expected serialization error"),
+        }
+
+        inputs.share_y.as_mut().unwrap().add_assign(&Fr::one());
+        match inputs.check_witness(&rln_test.hasher()) {
+            Err(RlnError::UnsatisfiedWitness(_)) => (),
+            _ => panic!("expected unsatisfied witness"),
+        }
+
+        inputs.share_y = None;
+        assert!(inputs.write(&mut Vec::new()).is_err());
+
+        let invalid_fr = vec![0xffu8; 32];
+        match RLNInputs::<Bn256>::read_public_inputs(invalid_fr.as_slice()) {
+            Err(RlnError::InvalidFieldElement(_)) => (),
+            _ => panic!("expected invalid field element"),
+        }
+    }
 
     #[test]
     fn test_input_serialization() {
@@ -426,6 +682,7 @@ mod test {
         let share_x = Fr::from_str("1").unwrap();
         let share_y = Fr::from_str("2").unwrap();
         let epoch = Fr::from_str("3").unwrap();
+        let rln_identifier = Fr::from_str("7").unwrap();
         let nullifier = Fr::from_str("4").unwrap();
         let root = Fr::from_str("5").unwrap();
         let id_key = Fr::from_str("6").unwrap();
@@ -439,6 +696,7 @@ mod test {
             share_x: Some(share_x),
             share_y: Some(share_y),
             epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
             nullifier: Some(nullifier),
             root: Some(root),
             id_key: Some(id_key),
@@ -451,6 +709,7 @@ mod test {
         assert_eq!(input0.share_x, input1.share_x);
         assert_eq!(input0.share_y, input1.share_y);
         assert_eq!(input0.epoch, input1.epoch);
+        assert_eq!(input0.rln_identifier, input1.rln_identifier);
         assert_eq!(input0.nullifier, input1.nullifier);
         assert_eq!(input0.root, input1.root);
         assert_eq!(input0.id_key, input1.id_key);
@@ -0,0 +1,676 @@
+use crate::circuit::gadgets::{enforce_equal, merkle_membership, nullifier};
+use crate::circuit::hasher::HasherGadget;
+use crate::circuit::polynomial::allocate_horner;
+use crate::circuit::poseidon::PoseidonCircuit;
+use crate::circuit::rln::{value, RLNInputs};
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::merkle::MembershipTree;
+use crate::utils::{interpolate_at_zero, read_fr, read_inputs};
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{Circuit, ConstraintSystem, SynthesisError};
+use sapling_crypto::circuit::{num, Assignment};
+
+use std::io::{self, Read, Write};
+
+// Rate Limit Nullifier over a secret polynomial of degree k
+//
+// Shares lie on a_0 + a_1 * x + ... + a_k * x^k where a_0 is id_key
+// and a_i = H(a_{i-1}, H(epoch, rln_identifier)), so a member may send k signals
+// per epoch of the application and the k + 1-th reveals a_0.
+// The nullifier is H(a_1) as in RLNCircuit.
+
+#[derive(Clone)]
+pub struct RLNPolyInputs<E>
+where
+    E: Engine,
+{
+    // degree of the secret polynomial, fixed by the circuit parameters
+    pub degree: usize,
+
+    // Public inputs
+
+    // share (x, y) where x should be hash of the signal
+    pub share_x: Option<E::Fr>,
+    pub share_y: Option<E::Fr>,
+
+    // epoch together with rln_identifier makes the external nullifier
+    pub epoch: Option<E::Fr>,
+
+    // rln_identifier tags the application
+    pub rln_identifier: Option<E::Fr>,
+
+    // nullifier
+    pub nullifier: Option<E::Fr>,
+
+    // root is the current state of membership set
+    pub root: Option<E::Fr>,
+
+    // Private inputs
+
+    // id_key must be a preimage of a leaf in membership tree
+    pub id_key: Option<E::Fr>,
+
+    // authentication path of the member
+    pub auth_path: Vec<Option<(E::Fr, bool)>>,
+}
+
+// coefficients a_0, ..., a_degree of the secret polynomial of a member
+// in an epoch of the application
+pub fn coefficients<E: Engine, H: Hasher<E>>(
+    hasher: &H,
+    id_key: E::Fr,
+    epoch: E::Fr,
+    rln_identifier: E::Fr,
+    degree: usize,
+) -> Result<Vec<E::Fr>, RlnError> {
+    if degree == 0 {
+        return Err(RlnError::InvalidDegree(degree));
+    }
+    let external_nullifier = RLNInputs::<E>::external_nullifier(hasher, epoch, rln_identifier)?;
+    let mut coeffs = vec![id_key];
+    for i in 0..degree {
+        let a_i = hasher.hash(vec![coeffs[i], external_nullifier])?;
+        coeffs.push(a_i);
+    }
+    Ok(coeffs)
+}
+
+// horner evaluation of the polynomial with the given coefficients
+pub fn evaluate<E: Engine>(coeffs: &[E::Fr], x: E::Fr) -> E::Fr {
+    let mut acc = E::Fr::zero();
+    for a_i in coeffs.iter().rev() {
+        acc.mul_assign(&x);
+        acc.add_assign(a_i);
+    }
+    acc
+}
+
+// recovers id_key from degree + 1 shares published under the nullifier
+// in the epoch of the application
+pub fn recover_id_secret<E: Engine, H: Hasher<E>>(
+    hasher: &H,
+    shares: &[(E::Fr, E::Fr)],
+    degree: usize,
+    epoch: E::Fr,
+    rln_identifier: E::Fr,
+    nullifier: E::Fr,
+) -> Result<E::Fr, RlnError> {
+    if degree == 0 {
+        return Err(RlnError::InvalidDegree(degree));
+    }
+    if shares.len() <= degree {
+        return Err(RlnError::InvalidShares("not enough shares"));
+    }
+    let id_key = interpolate_at_zero::<E>(&shares[..degree + 1])?;
+    let coeffs = coefficients::<E, H>(hasher, id_key, epoch, rln_identifier, degree)?;
+    if hasher.hash(vec![coeffs[1]])? != nullifier {
+        return Err(RlnError::InvalidShares("shares do not open the nullifier"));
+    }
+    if shares.iter().any(|(x, y)| evaluate::<E>(&coeffs, *x) != *y) {
+        return Err(RlnError::InvalidShares("share off the polynomial"));
+    }
+    Ok(id_key)
+}
+
+impl<E> RLNPolyInputs<E>
+where
+    E: Engine,
+{
+    pub fn public_inputs(&self) -> Result<Vec<E::Fr>, RlnError> {
+        Ok(vec![
+            value(self.root)?,
+            value(self.epoch)?,
+            value(self.share_x)?,
+            value(self.share_y)?,
+            value(self.nullifier)?,
+            value(self.rln_identifier)?,
+        ])
+    }
+
+    pub fn merkle_depth(&self) -> usize {
+        self.auth_path.len()
+    }
+
+    pub fn empty(merkle_depth: usize, degree: usize) -> RLNPolyInputs<E> {
+        RLNPolyInputs::<E> {
+            degree,
+            share_x: None,
+            share_y: None,
+            epoch: None,
+            rln_identifier: None,
+            nullifier: None,
+            root: None,
+            id_key: None,
+            auth_path: vec![None; merkle_depth],
+        }
+    }
+
+    // builds the full witness of a member signalling in an epoch,
+    // share_x is expected to be the hash of the signal,
+    // hashing is done with the hasher of the tree
+    pub fn from_identity<T: MembershipTree<E>>(
+        id_key: E::Fr,
+        degree: usize,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        share_x: E::Fr,
+    ) -> Result<RLNPolyInputs<E>, RlnError> {
+        let hasher = membership_tree.hasher();
+        let coeffs = coefficients::<E, _>(hasher, id_key, epoch, rln_identifier, degree)?;
+        let auth_path = membership_tree.witness(leaf_index)?;
+        let nullifier = hasher.hash(vec![coeffs[1]])?;
+
+        Ok(RLNPolyInputs::<E> {
+            degree,
+            share_x: Some(share_x),
+            share_y: Some(evaluate::<E>(&coeffs, share_x)),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(nullifier),
+            root: Some(membership_tree.root()),
+            id_key: Some(id_key),
+            auth_path: auth_path.into_iter().map(Some).collect(),
+        })
+    }
+
+    // checks natively that the private inputs satisfy the circuit,
+    // hasher must be the native counterpart of the one of the circuit
+    pub fn check_witness<H: Hasher<E>>(&self, hasher: &H) -> Result<(), RlnError> {
+        let id_key = value(self.id_key)?;
+
+        let mut acc = hasher.hash(vec![id_key])?;
+        for e in self.auth_path.iter() {
+            let (path_element, position) = value(*e)?;
+            acc = if position {
+                hasher.hash(vec![acc, path_element])?
+            } else {
+                hasher.hash(vec![path_element, acc])?
+            };
+        }
+        if acc != value(self.root)? {
+            return Err(RlnError::UnsatisfiedWitness("membership"));
+        }
+
+        let coeffs = coefficients::<E, H>(
+            hasher,
+            id_key,
+            value(self.epoch)?,
+            value(self.rln_identifier)?,
+            self.degree,
+        )?;
+        if evaluate::<E>(&coeffs, value(self.share_x)?) != value(self.share_y)? {
+            return Err(RlnError::UnsatisfiedWitness("polynomial equation"));
+        }
+
+        if hasher.hash(vec![coeffs[1]])? != value(self.nullifier)? {
+            return Err(RlnError::UnsatisfiedWitness("nullifier"));
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<RLNPolyInputs<E>, RlnError> {
+        let share_x = read_fr::<_, E>(&mut reader)?;
+        let share_y = read_fr::<_, E>(&mut reader)?;
+        let epoch = read_fr::<_, E>(&mut reader)?;
+        let rln_identifier = read_fr::<_, E>(&mut reader)?;
+        let nullifier = read_fr::<_, E>(&mut reader)?;
+        let root = read_fr::<_, E>(&mut reader)?;
+        let id_key = read_fr::<_, E>(&mut reader)?;
+        let mut byte_buf = [0u8; 1];
+        reader.read_exact(&mut byte_buf)?;
+        let degree = byte_buf[0] as usize;
+        if degree == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid degree").into());
+        }
+        let auth_path = RLNInputs::<E>::decode_auth_path(&mut reader)?;
+        Ok(RLNPolyInputs {
+            degree,
+            share_x: Some(share_x),
+            share_y: Some(share_y),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(nullifier),
+            root: Some(root),
+            id_key: Some(id_key),
+            auth_path,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        if self.degree == 0 || self.degree > 255 {
+            return Err(RlnError::InvalidDegree(self.degree));
+        }
+        value(self.share_x)?.into_repr().write_le(&mut writer)?;
+        value(self.share_y)?.into_repr().write_le(&mut writer)?;
+        value(self.epoch)?.into_repr().write_le(&mut writer)?;
+        value(self.rln_identifier)?
+            .into_repr()
+            .write_le(&mut writer)?;
+        value(self.nullifier)?.into_repr().write_le(&mut writer)?;
+        value(self.root)?.into_repr().write_le(&mut writer)?;
+        value(self.id_key)?.into_repr().write_le(&mut writer)?;
+        writer.write_all(&[self.degree as u8])?;
+        RLNInputs::<E>::encode_auth_path(&mut writer, self.auth_path.clone())?;
+        Ok(())
+    }
+
+    // root, epoch, share_x, share_y, nullifier, rln_identifier
+    pub fn read_public_inputs<R: Read>(reader: R) -> Result<Vec<E::Fr>, RlnError> {
+        read_inputs::<R, E>(reader, 6)
+    }
+
+    pub fn write_public_inputs<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        for input in self.public_inputs()?.iter() {
+            input.into_repr().write_le(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RLNPolyCircuit<E, H = PoseidonCircuit<E>>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    pub inputs: RLNPolyInputs<E>,
+    pub hasher: H,
+}
+
+impl<E, H> Circuit<E> for RLNPolyCircuit<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        if self.inputs.degree == 0 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // 1. Part
+        // Membership constraints
+        // root == merkle_proof(auth_path, preimage_of_leaf)
+
+        let mut hasher = self.hasher.clone();
+
+        // allocated early, made public after the nullifier
+        // to keep the order of public inputs
+        let rln_identifier = num::AllocatedNum::alloc(cs.namespace(|| "rln identifier"), || {
+            let value = self.inputs.rln_identifier;
+            Ok(*value.get()?)
+        })?;
+
+        let root = num::AllocatedNum::alloc(cs.namespace(|| "root"), || {
+            let value = self.inputs.root;
+            Ok(*value.get()?)
+        })?;
+        root.inputize(cs.namespace(|| "root is public"))?;
+
+        let preimage = num::AllocatedNum::alloc(cs.namespace(|| "preimage"), || {
+            let value = self.inputs.id_key;
+            Ok(*value.get()?)
+        })?;
+
+        let identity = hasher.alloc(cs.namespace(|| "identity"), vec![preimage.clone()])?;
+
+        merkle_membership(
+            cs.namespace(|| "membership"),
+            &mut hasher,
+            &identity,
+            &self.inputs.auth_path,
+            &root,
+        )?;
+
+        // 2. Part
+        // Polynomial Equation Constaints
+        // a_i = hash(a_{i-1}, hash(epoch, rln_identifier))
+        // share_y == a_0 + a_1 * share_x + ... + a_k * share_x^k
+
+        let epoch = num::AllocatedNum::alloc(cs.namespace(|| "epoch"), || {
+            let value = self.inputs.epoch;
+            Ok(*value.get()?)
+        })?;
+        epoch.inputize(cs.namespace(|| "epoch is public"))?;
+
+        let external_nullifier = hasher.alloc(
+            cs.namespace(|| "external nullifier"),
+            vec![epoch, rln_identifier.clone()],
+        )?;
+
+        let mut coeffs = vec![preimage];
+        for i in 1..=self.inputs.degree {
+            let a_i = hasher.alloc(
+                cs.namespace(|| format!("a_{}", i)),
+                vec![coeffs[i - 1].clone(), external_nullifier.clone()],
+            )?;
+            coeffs.push(a_i);
+        }
+
+        let share_x = num::AllocatedNum::alloc(cs.namespace(|| "share x"), || {
+            let value = self.inputs.share_x;
+            Ok(*value.get()?)
+        })?;
+        share_x.inputize(cs.namespace(|| "share x is public"))?;
+
+        let eval = allocate_horner(cs.namespace(|| "eval"), &coeffs, &share_x)?;
+
+        let share_y = num::AllocatedNum::alloc(cs.namespace(|| "share y"), || {
+            let value = self.inputs.share_y;
+            Ok(*value.get()?)
+        })?;
+        share_y.inputize(cs.namespace(|| "share y is public"))?;
+
+        enforce_equal(cs.namespace(|| "enforce lookup"), &share_y, &eval)?;
+
+        // 3. Part
+        // Nullifier constraints
+        // nullifier == hash(a_1)
+
+        let nullifier_value = num::AllocatedNum::alloc(cs.namespace(|| "nullifier"), || {
+            let value = self.inputs.nullifier;
+            Ok(*value.get()?)
+        })?;
+        nullifier_value.inputize(cs.namespace(|| "nullifier is public"))?;
+
+        rln_identifier.inputize(cs.namespace(|| "rln identifier is public"))?;
+
+        nullifier(
+            cs.namespace(|| "nullifier check"),
+            &mut hasher,
+            &coeffs[1],
+            &nullifier_value,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{recover_id_secret, RLNPolyCircuit, RLNPolyInputs};
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::error::RlnError;
+    use crate::merkle::MerkleTree;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use crate::utils::{interpolate_at_zero, interpolate_line};
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+    use sapling_crypto::bellman::Circuit;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    fn rln_identifier() -> Fr {
+        Fr::from_str("100").unwrap()
+    }
+
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654])
+    }
+
+    // tree with the member at leaf 6
+    fn membership(merkle_depth: usize, id_key: Fr) -> MerkleTree<Bn256> {
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let mut tree = MerkleTree::empty(hasher.clone(), merkle_depth).unwrap();
+        tree.update(6, hasher.hash(vec![id_key]).unwrap()).unwrap();
+        tree
+    }
+
+    fn synthesize(inputs: RLNPolyInputs<Bn256>) -> (bool, usize) {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNPolyCircuit::<Bn256> {
+            inputs,
+            hasher: PoseidonCircuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.find_unconstrained().is_empty());
+        (cs.is_satisfied(), cs.num_constraints())
+    }
+
+    #[test]
+    fn test_rln_poly_bn() {
+        let mut rng = rng();
+        let merkle_depth = 3;
+        let id_key = Fr::rand(&mut rng);
+        let epoch = Fr::rand(&mut rng);
+        let tree = membership(merkle_depth, id_key);
+
+        for degree in [1, 2, 3, 5].iter().cloned() {
+            let inputs = RLNPolyInputs::<Bn256>::from_identity(
+                id_key,
+                degree,
+                &tree,
+                6,
+                epoch,
+                rln_identifier(),
+                Fr::rand(&mut rng),
+            )
+            .unwrap();
+            inputs.check_witness(&PoseidonHasher::new()).unwrap();
+            let (satisfied, num_constraints) = synthesize(inputs.clone());
+            assert!(satisfied);
+            println!("bn256, merkle depth: {}, degree: {}", merkle_depth, degree);
+            println!("number of constatins:\t{}", num_constraints);
+
+            let mut bad = inputs.clone();
+            bad.share_y.as_mut().unwrap().add_assign(&Fr::one());
+            assert!(bad.check_witness(&PoseidonHasher::new()).is_err());
+            assert!(!synthesize(bad).0);
+        }
+
+        let degree = 2;
+        let circuit = RLNPolyCircuit::<Bn256> {
+            inputs: RLNPolyInputs::empty(merkle_depth, degree),
+            hasher: PoseidonCircuit::new(),
+        };
+        let parameters = generate_random_parameters(circuit, &mut rng).unwrap();
+        let inputs = RLNPolyInputs::from_identity(
+            id_key,
+            degree,
+            &tree,
+            6,
+            epoch,
+            rln_identifier(),
+            Fr::rand(&mut rng),
+        )
+        .unwrap();
+        let circuit = RLNPolyCircuit::<Bn256> {
+            inputs: inputs.clone(),
+            hasher: PoseidonCircuit::new(),
+        };
+        let proof = create_random_proof(circuit, &parameters, &mut rng).unwrap();
+        let verifying_key = prepare_verifying_key(&parameters.vk);
+        assert!(verify_proof(&verifying_key, &proof, &inputs.public_inputs().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_degree_one_is_a_line() {
+        let mut rng = rng();
+        let id_key = Fr::rand(&mut rng);
+        let epoch = Fr::rand(&mut rng);
+        let tree = membership(3, id_key);
+        let share = |share_x: Fr| {
+            let inputs = RLNPolyInputs::<Bn256>::from_identity(
+                id_key,
+                1,
+                &tree,
+                6,
+                epoch,
+                rln_identifier(),
+                share_x,
+            )
+            .unwrap();
+            (inputs.share_x.unwrap(), inputs.share_y.unwrap())
+        };
+        let (a_0, a_1) =
+            interpolate_line::<Bn256>(share(Fr::rand(&mut rng)), share(Fr::rand(&mut rng)))
+                .unwrap();
+        assert_eq!(a_0, id_key);
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let external_nullifier = hasher.hash(vec![epoch, rln_identifier()]).unwrap();
+        assert_eq!(a_1, hasher.hash(vec![id_key, external_nullifier]).unwrap());
+    }
+
+    #[test]
+    fn test_polynomial_recovery() {
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let mut rng = rng();
+        let id_key = Fr::rand(&mut rng);
+        let epoch = Fr::rand(&mut rng);
+        let tree = membership(3, id_key);
+
+        for degree in [1, 2, 4, 7].iter().cloned() {
+            let signals: Vec<RLNPolyInputs<Bn256>> = (0..=degree)
+                .map(|_| {
+                    RLNPolyInputs::from_identity(
+                        id_key,
+                        degree,
+                        &tree,
+                        6,
+                        epoch,
+                        rln_identifier(),
+                        Fr::rand(&mut rng),
+                    )
+                    .unwrap()
+                })
+                .collect();
+            let nullifier = signals[0].nullifier.unwrap();
+            assert!(signals.iter().all(|s| s.nullifier == Some(nullifier)));
+            let shares: Vec<(Fr, Fr)> = signals
+                .iter()
+                .map(|s| (s.share_x.unwrap(), s.share_y.unwrap()))
+                .collect();
+
+            assert_eq!(
+                recover_id_secret(&hasher, &shares, degree, epoch, rln_identifier(), nullifier)
+                    .unwrap(),
+                id_key
+            );
+
+            // shares of another application
+            let other_identifier = Fr::from_str("101").unwrap();
+            assert!(recover_id_secret(
+                &hasher,
+                &shares,
+                degree,
+                epoch,
+                other_identifier,
+                nullifier
+            )
+            .is_err());
+
+            // k shares reveal nothing
+            match recover_id_secret(
+                &hasher,
+                &shares[..degree],
+                degree,
+                epoch,
+                rln_identifier(),
+                nullifier,
+            ) {
+                Err(RlnError::InvalidShares(_)) => (),
+                _ => panic!("expected not enough shares"),
+            }
+            assert_ne!(
+                interpolate_at_zero::<Bn256>(&shares[..degree]).unwrap(),
+                id_key
+            );
+
+            let mut repeated = shares.clone();
+            repeated[degree] = repeated[0];
+            assert!(recover_id_secret(
+                &hasher,
+                &repeated,
+                degree,
+                epoch,
+                rln_identifier(),
+                nullifier
+            )
+            .is_err());
+        }
+        assert!(recover_id_secret(&hasher, &[], 0, epoch, rln_identifier(), Fr::zero()).is_err());
+    }
+
+    #[test]
+    fn test_rln_poly_poseidon2() {
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        use crate::poseidon2::Poseidon2;
+
+        let mut rng = rng();
+        let hasher = Poseidon2::<Bn256>::new();
+        let id_key = Fr::rand(&mut rng);
+        let epoch = Fr::rand(&mut rng);
+        let mut tree = MerkleTree::empty(hasher.clone(), 3).unwrap();
+        tree.update(6, hasher.hash(vec![id_key]).unwrap()).unwrap();
+
+        let degree = 2;
+        let signals: Vec<RLNPolyInputs<Bn256>> = (0..=degree)
+            .map(|_| {
+                RLNPolyInputs::from_identity(
+                    id_key,
+                    degree,
+                    &tree,
+                    6,
+                    epoch,
+                    rln_identifier(),
+                    Fr::rand(&mut rng),
+                )
+                .unwrap()
+            })
+            .collect();
+        signals[0].check_witness(&hasher).unwrap();
+        assert!(signals[0].check_witness(&PoseidonHasher::new()).is_err());
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNPolyCircuit {
+            inputs: signals[0].clone(),
+            hasher: Poseidon2Circuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(!synthesize(signals[0].clone()).0);
+
+        let shares: Vec<(Fr, Fr)> = signals
+            .iter()
+            .map(|s| (s.share_x.unwrap(), s.share_y.unwrap()))
+            .collect();
+        let nullifier = signals[0].nullifier.unwrap();
+        assert_eq!(
+            recover_id_secret(&hasher, &shares, degree, epoch, rln_identifier(), nullifier)
+                .unwrap(),
+            id_key
+        );
+    }
+
+    #[test]
+    fn test_input_serialization() {
+        let mut rng = rng();
+        let id_key = Fr::rand(&mut rng);
+        let tree = membership(4, id_key);
+        let inputs = RLNPolyInputs::<Bn256>::from_identity(
+            id_key,
+            3,
+            &tree,
+            6,
+            Fr::rand(&mut rng),
+            rln_identifier(),
+            Fr::rand(&mut rng),
+        )
+        .unwrap();
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        inputs.write(&mut raw_inputs).unwrap();
+        let restored = RLNPolyInputs::<Bn256>::read(raw_inputs.as_slice()).unwrap();
+        assert_eq!(restored.degree, 3);
+        assert_eq!(
+            inputs.public_inputs().unwrap(),
+            restored.public_inputs().unwrap()
+        );
+        assert_eq!(inputs.id_key, restored.id_key);
+        assert_eq!(inputs.auth_path, restored.auth_path);
+    }
+}
@@ -0,0 +1,402 @@
+use crate::circuit::hasher::HasherGadget;
+use crate::circuit::poseidon::PoseidonCircuit;
+use crate::circuit::rln::{synthesize_rln, value, RLNInputs};
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::merkle::MembershipTree;
+use crate::utils::{read_fr, read_inputs};
+use sapling_crypto::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{Circuit, ConstraintSystem, SynthesisError};
+use sapling_crypto::circuit::{num, Assignment};
+
+use std::io::{self, Read, Write};
+
+// Rate Limit Nullifier bound to the signal content
+//
+// The signal is split in field limbs which are public inputs,
+// and share_x is constrained to H(limbs) instead of being a free input.
+// Public inputs are the ones of RLNCircuit followed by the limbs.
+
+// bytes of signal per limb, below the modulus of the supported curves
+pub const SIGNAL_LIMB_BYTES: usize = 31;
+
+// limbs of a signal, padded with a 0x01 byte and zeros
+// so that signals differing in trailing zeros have distinct limbs
+pub fn signal_limbs<E: Engine>(signal: &[u8], limbs: usize) -> Result<Vec<E::Fr>, RlnError> {
+    if limbs == 0 {
+        return Err(RlnError::InvalidSignal("no limbs"));
+    }
+    if signal.len() >= limbs * SIGNAL_LIMB_BYTES {
+        return Err(RlnError::InvalidSignal("signal does not fit the limbs"));
+    }
+    let mut padded = signal.to_vec();
+    padded.push(1);
+    padded.resize(limbs * SIGNAL_LIMB_BYTES, 0);
+
+    padded
+        .chunks(SIGNAL_LIMB_BYTES)
+        .map(|chunk| {
+            let mut buf = [0u8; 32];
+            buf[..SIGNAL_LIMB_BYTES].copy_from_slice(chunk);
+            let mut repr = <E::Fr as PrimeField>::Repr::default();
+            repr.read_le(&buf[..])?;
+            Ok(E::Fr::from_repr(repr)?)
+        })
+        .collect()
+}
+
+// share_x the circuit enforces for the limbs of a signal
+pub fn signal_share_x<E: Engine, H: Hasher<E>>(
+    hasher: &H,
+    limbs: &[E::Fr],
+) -> Result<E::Fr, RlnError> {
+    hasher.hash(limbs.to_vec())
+}
+
+#[derive(Clone)]
+pub struct RLNSignalInputs<E>
+where
+    E: Engine,
+{
+    pub inputs: RLNInputs<E>,
+
+    // limbs of the signal, their number is fixed by the circuit parameters
+    pub signal: Vec<Option<E::Fr>>,
+}
+
+impl<E> RLNSignalInputs<E>
+where
+    E: Engine,
+{
+    pub fn public_inputs(&self) -> Result<Vec<E::Fr>, RlnError> {
+        let mut public_inputs = self.inputs.public_inputs()?;
+        for limb in self.signal.iter() {
+            public_inputs.push(value(*limb)?);
+        }
+        Ok(public_inputs)
+    }
+
+    pub fn empty(merkle_depth: usize, limbs: usize) -> RLNSignalInputs<E> {
+        RLNSignalInputs {
+            inputs: RLNInputs::empty(merkle_depth),
+            signal: vec![None; limbs],
+        }
+    }
+
+    // builds the full witness of a member sending signal in an epoch,
+    // hashing is done with the hasher of the tree
+    pub fn from_identity<T: MembershipTree<E>>(
+        id_key: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        signal: &[u8],
+        limbs: usize,
+    ) -> Result<RLNSignalInputs<E>, RlnError> {
+        let signal = signal_limbs::<E>(signal, limbs)?;
+        let inputs = RLNInputs::from_identity(
+            id_key,
+            membership_tree,
+            leaf_index,
+            epoch,
+            rln_identifier,
+            signal_share_x::<E, _>(membership_tree.hasher(), &signal)?,
+        )?;
+        Ok(RLNSignalInputs {
+            inputs,
+            signal: signal.into_iter().map(Some).collect(),
+        })
+    }
+
+    // checks natively that the private inputs satisfy the circuit,
+    // hasher must be the native counterpart of the one of the circuit
+    pub fn check_witness<H: Hasher<E>>(&self, hasher: &H) -> Result<(), RlnError> {
+        let signal = self
+            .signal
+            .iter()
+            .map(|limb| value(*limb))
+            .collect::<Result<Vec<_>, _>>()?;
+        if signal_share_x::<E, H>(hasher, &signal)? != value(self.inputs.share_x)? {
+            return Err(RlnError::UnsatisfiedWitness("signal hash"));
+        }
+        self.inputs.check_witness(hasher)
+    }
+
+    // inputs of RLNCircuit followed by the number of limbs and the limbs
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        if self.signal.is_empty() || self.signal.len() > 255 {
+            return Err(RlnError::InvalidSignal("invalid number of limbs"));
+        }
+        self.inputs.write(&mut writer)?;
+        writer.write_all(&[self.signal.len() as u8])?;
+        for limb in self.signal.iter() {
+            value(*limb)?.into_repr().write_le(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<RLNSignalInputs<E>, RlnError> {
+        let inputs = RLNInputs::read(&mut reader)?;
+        let mut byte_buf = [0u8; 1];
+        reader.read_exact(&mut byte_buf)?;
+        let limbs = byte_buf[0] as usize;
+        if limbs == 0 {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "invalid number of limbs").into(),
+            );
+        }
+        let mut signal = Vec::with_capacity(limbs);
+        for _ in 0..limbs {
+            signal.push(Some(read_fr::<_, E>(&mut reader)?));
+        }
+        Ok(RLNSignalInputs { inputs, signal })
+    }
+
+    pub fn read_public_inputs<R: Read>(reader: R, limbs: usize) -> Result<Vec<E::Fr>, RlnError> {
+        read_inputs::<R, E>(reader, 6 + limbs)
+    }
+
+    pub fn write_public_inputs<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        for input in self.public_inputs()? {
+            input.into_repr().write_le(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RLNSignalCircuit<E, H = PoseidonCircuit<E>>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    pub inputs: RLNSignalInputs<E>,
+    pub hasher: H,
+}
+
+impl<E, H> Circuit<E> for RLNSignalCircuit<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut hasher = self.hasher.clone();
+
+        let preimage = num::AllocatedNum::alloc(cs.namespace(|| "preimage"), || {
+            let value = self.inputs.inputs.id_key;
+            Ok(*value.get()?)
+        })?;
+
+        let mut signal = Vec::with_capacity(self.inputs.signal.len());
+        for (i, limb) in self.inputs.signal.iter().enumerate() {
+            signal.push(num::AllocatedNum::alloc(
+                cs.namespace(|| format!("signal limb {}", i)),
+                || Ok(*limb.get()?),
+            )?);
+        }
+
+        // share_x == hash(signal limbs)
+
+        let share_x = hasher.alloc(cs.namespace(|| "signal hash"), signal.clone())?;
+
+        synthesize_rln(cs, &self.inputs.inputs, hasher, preimage, share_x)?;
+
+        // limbs are public after the inputs of RLNCircuit
+
+        for (i, limb) in signal.iter().enumerate() {
+            limb.inputize(cs.namespace(|| format!("signal limb {} is public", i)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        signal_limbs, signal_share_x, RLNSignalCircuit, RLNSignalInputs, SIGNAL_LIMB_BYTES,
+    };
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::circuit::rln::RLNInputs;
+    use crate::merkle::MerkleTree;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+    use sapling_crypto::bellman::Circuit;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654])
+    }
+
+    fn membership(merkle_depth: usize, id_key: Fr) -> MerkleTree<Bn256> {
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let mut tree = MerkleTree::empty(hasher.clone(), merkle_depth).unwrap();
+        tree.update(6, hasher.hash(vec![id_key]).unwrap()).unwrap();
+        tree
+    }
+
+    fn synthesize(inputs: RLNSignalInputs<Bn256>) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNSignalCircuit::<Bn256> {
+            inputs,
+            hasher: PoseidonCircuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.find_unconstrained().is_empty());
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn test_signal_limbs() {
+        let one = signal_limbs::<Bn256>(b"a", 1).unwrap();
+        assert_eq!(one, vec![Fr::from_str("353").unwrap()]);
+        assert_ne!(one, signal_limbs::<Bn256>(b"a\0", 1).unwrap());
+
+        let full = vec![0xffu8; 2 * SIGNAL_LIMB_BYTES - 1];
+        let limbs = signal_limbs::<Bn256>(&full, 2).unwrap();
+        assert_eq!(limbs.len(), 2);
+        assert!(signal_limbs::<Bn256>(&[full.as_slice(), b"x"].concat(), 2).is_err());
+        assert!(signal_limbs::<Bn256>(b"", 0).is_err());
+        assert_eq!(signal_limbs::<Bn256>(b"", 3).unwrap()[1], Fr::zero());
+    }
+
+    #[test]
+    fn test_rln_signal_poseidon2() {
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        use crate::poseidon2::Poseidon2;
+
+        let mut rng = rng();
+        let hasher = Poseidon2::<Bn256>::new();
+        let id_key = Fr::rand(&mut rng);
+        let mut tree = MerkleTree::empty(hasher.clone(), 3).unwrap();
+        tree.update(6, hasher.hash(vec![id_key]).unwrap()).unwrap();
+
+        let limbs = 2;
+        let inputs = RLNSignalInputs::<Bn256>::from_identity(
+            id_key,
+            &tree,
+            6,
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+            b"hello world",
+            limbs,
+        )
+        .unwrap();
+        inputs.check_witness(&hasher).unwrap();
+        assert!(inputs.check_witness(&PoseidonHasher::new()).is_err());
+        let signal = signal_limbs::<Bn256>(b"hello world", limbs).unwrap();
+        assert_eq!(
+            inputs.inputs.share_x.unwrap(),
+            signal_share_x(&hasher, &signal).unwrap()
+        );
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNSignalCircuit {
+            inputs: inputs.clone(),
+            hasher: Poseidon2Circuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(!synthesize(inputs));
+    }
+
+    #[test]
+    fn test_rln_signal() {
+        let mut rng = rng();
+        let merkle_depth = 3;
+        let id_key = Fr::rand(&mut rng);
+        let epoch = Fr::rand(&mut rng);
+        let rln_identifier = Fr::rand(&mut rng);
+        let tree = membership(merkle_depth, id_key);
+
+        for limbs in 1..=3 {
+            let inputs = RLNSignalInputs::<Bn256>::from_identity(
+                id_key,
+                &tree,
+                6,
+                epoch,
+                rln_identifier,
+                b"hello world",
+                limbs,
+            )
+            .unwrap();
+            inputs.check_witness(&PoseidonHasher::new()).unwrap();
+            assert!(synthesize(inputs.clone()));
+            let public_inputs = inputs.public_inputs().unwrap();
+            assert_eq!(public_inputs.len(), 6 + limbs);
+            let signal = signal_limbs::<Bn256>(b"hello world", limbs).unwrap();
+            assert_eq!(
+                public_inputs[2],
+                signal_share_x(&PoseidonHasher::<Bn256>::new(), &signal).unwrap()
+            );
+            assert_eq!(&public_inputs[6..], signal.as_slice());
+
+            // a valid RLN witness over share_x of another signal
+            let other = signal_limbs::<Bn256>(b"another signal", limbs).unwrap();
+            let mut bad = inputs.clone();
+            bad.inputs = RLNInputs::from_identity(
+                id_key,
+                &tree,
+                6,
+                epoch,
+                rln_identifier,
+                signal_share_x(&PoseidonHasher::<Bn256>::new(), &other).unwrap(),
+            )
+            .unwrap();
+            bad.inputs
+                .check_witness(&PoseidonHasher::<Bn256>::new())
+                .unwrap();
+            assert!(bad.check_witness(&PoseidonHasher::new()).is_err());
+            assert!(!synthesize(bad));
+        }
+
+        let limbs = 2;
+        let circuit = RLNSignalCircuit::<Bn256> {
+            inputs: RLNSignalInputs::empty(merkle_depth, limbs),
+            hasher: PoseidonCircuit::new(),
+        };
+        let parameters = generate_random_parameters(circuit, &mut rng).unwrap();
+        let inputs = RLNSignalInputs::from_identity(
+            id_key,
+            &tree,
+            6,
+            epoch,
+            rln_identifier,
+            b"hello world",
+            limbs,
+        )
+        .unwrap();
+        let circuit = RLNSignalCircuit::<Bn256> {
+            inputs: inputs.clone(),
+            hasher: PoseidonCircuit::new(),
+        };
+        let proof = create_random_proof(circuit, &parameters, &mut rng).unwrap();
+        let verifying_key = prepare_verifying_key(&parameters.vk);
+        let public_inputs = inputs.public_inputs().unwrap();
+        assert!(verify_proof(&verifying_key, &proof, &public_inputs).unwrap());
+
+        // the proof does not carry over to another signal under the same share
+        let mut relayed = public_inputs.clone();
+        relayed[6].add_assign(&Fr::one());
+        assert!(!verify_proof(&verifying_key, &proof, &relayed).unwrap());
+
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        inputs.write(&mut raw_inputs).unwrap();
+        let restored = RLNSignalInputs::<Bn256>::read(raw_inputs.as_slice()).unwrap();
+        assert_eq!(restored.signal, inputs.signal);
+        assert_eq!(restored.public_inputs().unwrap(), public_inputs);
+
+        let mut raw_public_inputs: Vec<u8> = Vec::new();
+        inputs.write_public_inputs(&mut raw_public_inputs).unwrap();
+        let read =
+            RLNSignalInputs::<Bn256>::read_public_inputs(raw_public_inputs.as_slice(), limbs)
+                .unwrap();
+        assert_eq!(read, public_inputs);
+    }
+}
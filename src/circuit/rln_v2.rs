@@ -0,0 +1,611 @@
+use crate::circuit::gadgets::{merkle_membership, nullifier, range_check, share_evaluation};
+use crate::circuit::hasher::HasherGadget;
+use crate::circuit::poseidon::PoseidonCircuit;
+use crate::circuit::rln::{value, RLNInputs};
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::merkle::MembershipTree;
+use crate::utils::{read_fr, read_inputs};
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{Circuit, ConstraintSystem, LinearCombination, SynthesisError};
+use sapling_crypto::circuit::{num, Assignment};
+
+use std::io::{Read, Write};
+
+// Rate Limit Nullifier with a per member message limit
+//
+// A member registers H(id_commitment, user_message_limit) as its leaf
+// and may send up to user_message_limit signals per epoch,
+// each under its own message_id and so its own line equation.
+// As in RLNCircuit the epoch is scoped to the application by
+// the external nullifier H(epoch, rln_identifier).
+
+// bit length of the message_id range check,
+// user_message_limit is at most 2^MESSAGE_LIMIT_BITS
+pub const MESSAGE_LIMIT_BITS: usize = 16;
+
+#[derive(Clone)]
+pub struct RLNV2Inputs<E>
+where
+    E: Engine,
+{
+    // Public inputs
+
+    // share (x, y) where x should be hash of the signal
+    pub share_x: Option<E::Fr>,
+    pub share_y: Option<E::Fr>,
+
+    // epoch together with rln_identifier makes the external nullifier
+    pub epoch: Option<E::Fr>,
+
+    // rln_identifier tags the application
+    pub rln_identifier: Option<E::Fr>,
+
+    // nullifier
+    pub nullifier: Option<E::Fr>,
+
+    // root is the current state of membership set
+    pub root: Option<E::Fr>,
+
+    // Private inputs
+
+    // id_key, H(id_key) is the identity commitment
+    pub id_key: Option<E::Fr>,
+
+    // number of signals the member may send in an epoch,
+    // bound to the member by the leaf
+    pub user_message_limit: Option<E::Fr>,
+
+    // index of the signal in the epoch, must be below user_message_limit
+    pub message_id: Option<E::Fr>,
+
+    // authentication path of the member
+    pub auth_path: Vec<Option<(E::Fr, bool)>>,
+}
+
+impl<E> RLNV2Inputs<E>
+where
+    E: Engine,
+{
+    pub fn public_inputs(&self) -> Result<Vec<E::Fr>, RlnError> {
+        Ok(vec![
+            value(self.root)?,
+            value(self.epoch)?,
+            value(self.share_x)?,
+            value(self.share_y)?,
+            value(self.nullifier)?,
+            value(self.rln_identifier)?,
+        ])
+    }
+
+    pub fn merkle_depth(&self) -> usize {
+        self.auth_path.len()
+    }
+
+    pub fn empty(merkle_depth: usize) -> RLNV2Inputs<E> {
+        RLNV2Inputs::<E> {
+            share_x: None,
+            share_y: None,
+            epoch: None,
+            rln_identifier: None,
+            nullifier: None,
+            root: None,
+            id_key: None,
+            user_message_limit: None,
+            message_id: None,
+            auth_path: vec![None; merkle_depth],
+        }
+    }
+
+    // leaf of a member, H(H(id_key), user_message_limit)
+    pub fn rate_commitment<H: Hasher<E>>(
+        hasher: &H,
+        id_key: E::Fr,
+        user_message_limit: E::Fr,
+    ) -> Result<E::Fr, RlnError> {
+        let id_comm = hasher.hash(vec![id_key])?;
+        hasher.hash(vec![id_comm, user_message_limit])
+    }
+
+    // builds the full witness of the message_id-th signal of a member in an epoch,
+    // share_x is expected to be the hash of the signal,
+    // hashing is done with the hasher of the tree
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_identity<T: MembershipTree<E>>(
+        id_key: E::Fr,
+        user_message_limit: E::Fr,
+        message_id: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        rln_identifier: E::Fr,
+        share_x: E::Fr,
+    ) -> Result<RLNV2Inputs<E>, RlnError> {
+        check_message_id::<E>(user_message_limit, message_id)?;
+        let hasher = membership_tree.hasher();
+        let auth_path = membership_tree.witness(leaf_index)?;
+
+        // a_1 = hash(a_0, hash(epoch, rln_identifier), message_id)
+        let a_0 = id_key;
+        let external_nullifier = RLNInputs::<E>::external_nullifier(hasher, epoch, rln_identifier)?;
+        let a_1 = hasher.hash(vec![a_0, external_nullifier, message_id])?;
+
+        // share_y = a_0 + a_1 * share_x
+        let mut share_y = a_1;
+        share_y.mul_assign(&share_x);
+        share_y.add_assign(&a_0);
+
+        let nullifier = hasher.hash(vec![a_1])?;
+
+        Ok(RLNV2Inputs::<E> {
+            share_x: Some(share_x),
+            share_y: Some(share_y),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(nullifier),
+            root: Some(membership_tree.root()),
+            id_key: Some(id_key),
+            user_message_limit: Some(user_message_limit),
+            message_id: Some(message_id),
+            auth_path: auth_path.into_iter().map(Some).collect(),
+        })
+    }
+
+    // checks natively that the private inputs satisfy the circuit,
+    // hasher must be the native counterpart of the one of the circuit
+    pub fn check_witness<H: Hasher<E>>(&self, hasher: &H) -> Result<(), RlnError> {
+        let id_key = value(self.id_key)?;
+        let user_message_limit = value(self.user_message_limit)?;
+        let message_id = value(self.message_id)?;
+
+        check_message_id::<E>(user_message_limit, message_id)?;
+
+        let mut acc = Self::rate_commitment(hasher, id_key, user_message_limit)?;
+        for e in self.auth_path.iter() {
+            let (path_element, position) = value(*e)?;
+            acc = if position {
+                hasher.hash(vec![acc, path_element])?
+            } else {
+                hasher.hash(vec![path_element, acc])?
+            };
+        }
+        if acc != value(self.root)? {
+            return Err(RlnError::UnsatisfiedWitness("membership"));
+        }
+
+        let external_nullifier = RLNInputs::<E>::external_nullifier(
+            hasher,
+            value(self.epoch)?,
+            value(self.rln_identifier)?,
+        )?;
+        let a_1 = hasher.hash(vec![id_key, external_nullifier, message_id])?;
+        let mut share_y = a_1;
+        share_y.mul_assign(&value(self.share_x)?);
+        share_y.add_assign(&id_key);
+        if share_y != value(self.share_y)? {
+            return Err(RlnError::UnsatisfiedWitness("line equation"));
+        }
+
+        if hasher.hash(vec![a_1])? != value(self.nullifier)? {
+            return Err(RlnError::UnsatisfiedWitness("nullifier"));
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<RLNV2Inputs<E>, RlnError> {
+        let share_x = read_fr::<_, E>(&mut reader)?;
+        let share_y = read_fr::<_, E>(&mut reader)?;
+        let epoch = read_fr::<_, E>(&mut reader)?;
+        let rln_identifier = read_fr::<_, E>(&mut reader)?;
+        let nullifier = read_fr::<_, E>(&mut reader)?;
+        let root = read_fr::<_, E>(&mut reader)?;
+        let id_key = read_fr::<_, E>(&mut reader)?;
+        let user_message_limit = read_fr::<_, E>(&mut reader)?;
+        let message_id = read_fr::<_, E>(&mut reader)?;
+        let auth_path = RLNInputs::<E>::decode_auth_path(&mut reader)?;
+        Ok(RLNV2Inputs {
+            share_x: Some(share_x),
+            share_y: Some(share_y),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(nullifier),
+            root: Some(root),
+            id_key: Some(id_key),
+            user_message_limit: Some(user_message_limit),
+            message_id: Some(message_id),
+            auth_path,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        value(self.share_x)?.into_repr().write_le(&mut writer)?;
+        value(self.share_y)?.into_repr().write_le(&mut writer)?;
+        value(self.epoch)?.into_repr().write_le(&mut writer)?;
+        value(self.rln_identifier)?
+            .into_repr()
+            .write_le(&mut writer)?;
+        value(self.nullifier)?.into_repr().write_le(&mut writer)?;
+        value(self.root)?.into_repr().write_le(&mut writer)?;
+        value(self.id_key)?.into_repr().write_le(&mut writer)?;
+        value(self.user_message_limit)?
+            .into_repr()
+            .write_le(&mut writer)?;
+        value(self.message_id)?.into_repr().write_le(&mut writer)?;
+        RLNInputs::<E>::encode_auth_path(&mut writer, self.auth_path.clone())?;
+        Ok(())
+    }
+
+    // root, epoch, share_x, share_y, nullifier, rln_identifier
+    pub fn read_public_inputs<R: Read>(reader: R) -> Result<Vec<E::Fr>, RlnError> {
+        read_inputs::<R, E>(reader, 6)
+    }
+
+    pub fn write_public_inputs<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        for input in self.public_inputs()?.iter() {
+            input.into_repr().write_le(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+fn u64_value<E: Engine>(e: E::Fr) -> Option<u64> {
+    let repr = e.into_repr();
+    let limbs = repr.as_ref();
+    if limbs[1..].iter().any(|limb| *limb != 0) {
+        return None;
+    }
+    Some(limbs[0])
+}
+
+// native counterpart of the range checks in the circuit
+fn check_message_id<E: Engine>(
+    user_message_limit: E::Fr,
+    message_id: E::Fr,
+) -> Result<(), RlnError> {
+    match (
+        u64_value::<E>(message_id),
+        u64_value::<E>(user_message_limit),
+    ) {
+        (Some(message_id), Some(user_message_limit))
+            if message_id < user_message_limit && user_message_limit <= 1 << MESSAGE_LIMIT_BITS =>
+        {
+            Ok(())
+        }
+        _ => Err(RlnError::UnsatisfiedWitness("message id range")),
+    }
+}
+
+#[derive(Clone)]
+pub struct RLNV2Circuit<E, H = PoseidonCircuit<E>>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    pub inputs: RLNV2Inputs<E>,
+    pub hasher: H,
+}
+
+impl<E, H> Circuit<E> for RLNV2Circuit<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        // 1. Part
+        // Membership constraints
+        // root == merkle_proof(auth_path, H(H(id_key), user_message_limit))
+
+        let mut hasher = self.hasher.clone();
+
+        // allocated early, made public after the nullifier
+        // to keep the order of public inputs
+        let rln_identifier = num::AllocatedNum::alloc(cs.namespace(|| "rln identifier"), || {
+            let value = self.inputs.rln_identifier;
+            Ok(*value.get()?)
+        })?;
+
+        let root = num::AllocatedNum::alloc(cs.namespace(|| "root"), || {
+            let value = self.inputs.root;
+            Ok(*value.get()?)
+        })?;
+        root.inputize(cs.namespace(|| "root is public"))?;
+
+        let preimage = num::AllocatedNum::alloc(cs.namespace(|| "preimage"), || {
+            let value = self.inputs.id_key;
+            Ok(*value.get()?)
+        })?;
+
+        let user_message_limit =
+            num::AllocatedNum::alloc(cs.namespace(|| "user message limit"), || {
+                let value = self.inputs.user_message_limit;
+                Ok(*value.get()?)
+            })?;
+
+        // rate commitment is a leaf of membership tree
+
+        let identity = hasher.alloc(cs.namespace(|| "identity"), vec![preimage.clone()])?;
+        let rate_commitment = hasher.alloc(
+            cs.namespace(|| "rate commitment"),
+            vec![identity, user_message_limit.clone()],
+        )?;
+
+        merkle_membership(
+            cs.namespace(|| "membership"),
+            &mut hasher,
+            &rate_commitment,
+            &self.inputs.auth_path,
+            &root,
+        )?;
+
+        // 2. Part
+        // Message id range constraints
+        // message_id < 2^bits and user_message_limit - 1 - message_id < 2^bits
+        // so that message_id < user_message_limit without wrapping around
+
+        let message_id = num::AllocatedNum::alloc(cs.namespace(|| "message id"), || {
+            let value = self.inputs.message_id;
+            Ok(*value.get()?)
+        })?;
+
+        range_check(
+            cs.namespace(|| "message id range"),
+            LinearCombination::zero() + message_id.get_variable(),
+            message_id.get_value(),
+            MESSAGE_LIMIT_BITS,
+        )?;
+
+        let remaining = match (user_message_limit.get_value(), message_id.get_value()) {
+            (Some(limit), Some(id)) => {
+                let mut remaining = limit;
+                remaining.sub_assign(&E::Fr::one());
+                remaining.sub_assign(&id);
+                Some(remaining)
+            }
+            _ => None,
+        };
+        range_check(
+            cs.namespace(|| "message id below limit"),
+            LinearCombination::zero() + user_message_limit.get_variable()
+                - CS::one()
+                - message_id.get_variable(),
+            remaining,
+            MESSAGE_LIMIT_BITS,
+        )?;
+
+        // 3. Part
+        // Line Equation Constaints
+        // a_1 = hash(a_0, hash(epoch, rln_identifier), message_id)
+        // share_y == a_0 + a_1 * share_x
+
+        let epoch = num::AllocatedNum::alloc(cs.namespace(|| "epoch"), || {
+            let value = self.inputs.epoch;
+            Ok(*value.get()?)
+        })?;
+        epoch.inputize(cs.namespace(|| "epoch is public"))?;
+
+        let a_0 = preimage;
+
+        let external_nullifier = hasher.alloc(
+            cs.namespace(|| "external nullifier"),
+            vec![epoch, rln_identifier.clone()],
+        )?;
+
+        let a_1 = hasher.alloc(
+            cs.namespace(|| "a_1"),
+            vec![a_0.clone(), external_nullifier, message_id],
+        )?;
+
+        let share_x = num::AllocatedNum::alloc(cs.namespace(|| "share x"), || {
+            let value = self.inputs.share_x;
+            Ok(*value.get()?)
+        })?;
+        share_x.inputize(cs.namespace(|| "share x is public"))?;
+
+        let share_y = num::AllocatedNum::alloc(cs.namespace(|| "share y"), || {
+            let value = self.inputs.share_y;
+            Ok(*value.get()?)
+        })?;
+        share_y.inputize(cs.namespace(|| "share y is public"))?;
+
+        share_evaluation(cs.namespace(|| "share"), &a_0, &a_1, &share_x, &share_y)?;
+
+        // 4. Part
+        // Nullifier constraints
+        // nullifier == hash(a_1)
+
+        let nullifier_value = num::AllocatedNum::alloc(cs.namespace(|| "nullifier"), || {
+            let value = self.inputs.nullifier;
+            Ok(*value.get()?)
+        })?;
+        nullifier_value.inputize(cs.namespace(|| "nullifier is public"))?;
+
+        rln_identifier.inputize(cs.namespace(|| "rln identifier is public"))?;
+
+        nullifier(
+            cs.namespace(|| "nullifier check"),
+            &mut hasher,
+            &a_1,
+            &nullifier_value,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{RLNV2Circuit, RLNV2Inputs};
+    use crate::circuit::bench;
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::error::RlnError;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+    use sapling_crypto::bellman::Circuit;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    struct TestSuite {
+        merkle_depth: usize,
+        user_message_limit: u64,
+    }
+
+    fn cases() -> Vec<TestSuite> {
+        vec![
+            TestSuite {
+                merkle_depth: 3,
+                user_message_limit: 1,
+            },
+            TestSuite {
+                merkle_depth: 24,
+                user_message_limit: 10,
+            },
+            TestSuite {
+                merkle_depth: 32,
+                user_message_limit: 1 << 16,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_rln_v2_bn() {
+        for case in cases().iter() {
+            let rln_test =
+                bench::RLNV2Test::<Bn256>::new(case.merkle_depth, case.user_message_limit);
+            let num_constraints = rln_test.synthesize(case.user_message_limit - 1);
+            let result = rln_test.run_prover_bench(0);
+            println!(
+                "bn256, merkle depth: {}, user message limit: {}",
+                case.merkle_depth, case.user_message_limit,
+            );
+            println!("number of constatins:\t{}", num_constraints);
+            println!("prover key size:\t{}", result.prover_key_size);
+            println!("prover time:\t{}", result.prover_time);
+        }
+    }
+
+    fn is_satisfied(inputs: &RLNV2Inputs<Bn256>) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNV2Circuit::<Bn256> {
+            inputs: inputs.clone(),
+            hasher: PoseidonCircuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn test_message_id_range() {
+        let rln_test = bench::RLNV2Test::<Bn256>::new(3, 4);
+
+        // every message id gets its own nullifier
+        let nullifiers: Vec<Fr> = (0..4)
+            .map(|id| rln_test.valid_inputs(id).nullifier.unwrap())
+            .collect();
+        for (i, a) in nullifiers.iter().enumerate() {
+            assert!(nullifiers[i + 1..].iter().all(|b| a != b));
+        }
+
+        // message_id == user_message_limit
+        let mut inputs = rln_test.valid_inputs(3);
+        inputs.message_id = Some(Fr::from_str("4").unwrap());
+        match inputs.check_witness(&PoseidonHasher::new()) {
+            Err(RlnError::UnsatisfiedWitness("message id range")) => (),
+            _ => panic!("expected message id out of range"),
+        }
+        assert!(!is_satisfied(&inputs));
+
+        // message_id wrapping around below zero
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        inputs.message_id = Some(minus_one);
+        assert!(inputs.check_witness(&PoseidonHasher::new()).is_err());
+        assert!(!is_satisfied(&inputs));
+
+        // a limit other than the registered one
+        let mut inputs = rln_test.valid_inputs(3);
+        inputs.user_message_limit = Some(Fr::from_str("5").unwrap());
+        match inputs.check_witness(&PoseidonHasher::new()) {
+            Err(RlnError::UnsatisfiedWitness("membership")) => (),
+            _ => panic!("expected membership failure"),
+        }
+        assert!(!is_satisfied(&inputs));
+    }
+
+    #[test]
+    fn test_rln_identifier() {
+        let rln_test = bench::RLNV2Test::<Bn256>::new(3, 4);
+        let inputs = rln_test.valid_inputs(1);
+
+        // the witness of another application does not open under this one
+        let mut other = inputs.clone();
+        other.rln_identifier = Some(Fr::from_str("101").unwrap());
+        match other.check_witness(&PoseidonHasher::new()) {
+            Err(RlnError::UnsatisfiedWitness("line equation")) => (),
+            _ => panic!("expected line equation failure"),
+        }
+        assert!(!is_satisfied(&other));
+        assert!(is_satisfied(&inputs));
+    }
+
+    #[test]
+    fn test_rln_v2_poseidon2() {
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        use crate::merkle::MerkleTree;
+        use crate::poseidon2::Poseidon2;
+
+        let hasher = Poseidon2::<Bn256>::new();
+        let mut tree = MerkleTree::empty(hasher.clone(), 3).unwrap();
+        let id_key = Fr::from_str("7").unwrap();
+        let user_message_limit = Fr::from_str("4").unwrap();
+        let rate_commitment =
+            RLNV2Inputs::<Bn256>::rate_commitment(&hasher, id_key, user_message_limit).unwrap();
+        tree.update(6, rate_commitment).unwrap();
+        let inputs = RLNV2Inputs::<Bn256>::from_identity(
+            id_key,
+            user_message_limit,
+            Fr::from_str("2").unwrap(),
+            &tree,
+            6,
+            Fr::from_str("1").unwrap(),
+            bench::RLNTest::<Bn256>::rln_identifier(),
+            Fr::from_str("3").unwrap(),
+        )
+        .unwrap();
+        inputs.check_witness(&hasher).unwrap();
+        assert!(inputs.check_witness(&PoseidonHasher::new()).is_err());
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNV2Circuit {
+            inputs: inputs.clone(),
+            hasher: Poseidon2Circuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(!is_satisfied(&inputs));
+    }
+
+    #[test]
+    fn test_input_serialization() {
+        let inputs = bench::RLNV2Test::<Bn256>::new(4, 3).valid_inputs(2);
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        inputs.write(&mut raw_inputs).unwrap();
+        let restored = RLNV2Inputs::<Bn256>::read(raw_inputs.as_slice()).unwrap();
+        assert_eq!(inputs.share_x, restored.share_x);
+        assert_eq!(inputs.share_y, restored.share_y);
+        assert_eq!(inputs.epoch, restored.epoch);
+        assert_eq!(inputs.rln_identifier, restored.rln_identifier);
+        assert_eq!(inputs.nullifier, restored.nullifier);
+        assert_eq!(inputs.root, restored.root);
+        assert_eq!(inputs.id_key, restored.id_key);
+        assert_eq!(inputs.user_message_limit, restored.user_message_limit);
+        assert_eq!(inputs.message_id, restored.message_id);
+        assert_eq!(inputs.auth_path, restored.auth_path);
+
+        let mut raw_public_inputs: Vec<u8> = Vec::new();
+        inputs.write_public_inputs(&mut raw_public_inputs).unwrap();
+        assert_eq!(
+            RLNV2Inputs::<Bn256>::read_public_inputs(raw_public_inputs.as_slice()).unwrap(),
+            inputs.public_inputs().unwrap()
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::RlnError;
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+
+// Canonical mapping from unix time to epochs.
+// Epoch n covers [n * epoch_length, (n + 1) * epoch_length) seconds,
+// and its external nullifier is the field element n.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpochConfig {
+    // epoch length in seconds
+    epoch_length: u64,
+    // number of epochs a received epoch may differ from the local one
+    max_skew: u64,
+}
+
+impl EpochConfig {
+    pub fn new(epoch_length: u64, max_skew: u64) -> Result<EpochConfig, RlnError> {
+        if epoch_length == 0 {
+            return Err(RlnError::InvalidEpoch("zero epoch length"));
+        }
+        Ok(EpochConfig {
+            epoch_length,
+            max_skew,
+        })
+    }
+
+    pub fn epoch_length(&self) -> u64 {
+        self.epoch_length
+    }
+
+    pub fn max_skew(&self) -> u64 {
+        self.max_skew
+    }
+
+    pub fn epoch_at<E: Engine>(&self, timestamp: u64) -> E::Fr {
+        let index = timestamp / self.epoch_length;
+        E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(index))
+            .expect("u64 is below the modulus")
+    }
+
+    // index of an epoch, fails for field elements that are not a u64
+    pub fn epoch_index<E: Engine>(&self, epoch: E::Fr) -> Result<u64, RlnError> {
+        let repr = epoch.into_repr();
+        let limbs = repr.as_ref();
+        if limbs[1..].iter().any(|limb| *limb != 0) {
+            return Err(RlnError::InvalidEpoch("epoch is not a u64"));
+        }
+        Ok(limbs[0])
+    }
+
+    // unix time at which the epoch starts
+    pub fn epoch_start<E: Engine>(&self, epoch: E::Fr) -> Result<u64, RlnError> {
+        self.epoch_index::<E>(epoch)?
+            .checked_mul(self.epoch_length)
+            .ok_or(RlnError::InvalidEpoch("epoch start overflows u64"))
+    }
+
+    // whether a received epoch is at most max_skew epochs away from the one at `now`
+    pub fn is_within_skew<E: Engine>(&self, epoch: E::Fr, now: u64) -> bool {
+        let index = match self.epoch_index::<E>(epoch) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+        index.abs_diff(now / self.epoch_length) <= self.max_skew
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn current_epoch<E: Engine>(&self) -> Result<E::Fr, RlnError> {
+        Ok(self.epoch_at::<E>(unix_now()?))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_now() -> Result<u64, RlnError> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|_| RlnError::InvalidEpoch("system clock is before unix epoch"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::EpochConfig;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+
+    #[test]
+    fn test_epoch_mapping() {
+        let epochs = EpochConfig::new(10, 1).unwrap();
+        let epoch = epochs.epoch_at::<Bn256>(1_600_000_005);
+        assert_eq!(epoch, Fr::from_str("160000000").unwrap());
+        assert_eq!(epochs.epoch_index::<Bn256>(epoch).unwrap(), 160_000_000);
+        assert_eq!(epochs.epoch_start::<Bn256>(epoch).unwrap(), 1_600_000_000);
+        assert_eq!(epochs.epoch_at::<Bn256>(1_600_000_009), epoch);
+        assert_ne!(epochs.epoch_at::<Bn256>(1_600_000_010), epoch);
+
+        let mut large = Fr::from_str("18446744073709551615").unwrap();
+        large.add_assign(&Fr::one());
+        assert!(epochs.epoch_index::<Bn256>(large).is_err());
+        let last = Fr::from_str("18446744073709551615").unwrap();
+        assert!(epochs.epoch_start::<Bn256>(last).is_err());
+
+        assert!(EpochConfig::new(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_epoch_skew() {
+        let epochs = EpochConfig::new(10, 1).unwrap();
+        let now = 1_000;
+        assert!(epochs.is_within_skew::<Bn256>(epochs.epoch_at::<Bn256>(now), now));
+        assert!(epochs.is_within_skew::<Bn256>(epochs.epoch_at::<Bn256>(now - 10), now));
+        assert!(epochs.is_within_skew::<Bn256>(epochs.epoch_at::<Bn256>(now + 19), now));
+        assert!(!epochs.is_within_skew::<Bn256>(epochs.epoch_at::<Bn256>(now - 11), now));
+        assert!(!epochs.is_within_skew::<Bn256>(epochs.epoch_at::<Bn256>(now + 20), now));
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        assert!(!epochs.is_within_skew::<Bn256>(minus_one, now));
+    }
+}
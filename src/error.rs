@@ -0,0 +1,99 @@
+use sapling_crypto::bellman::pairing::ff::PrimeFieldDecodingError;
+use sapling_crypto::bellman::SynthesisError;
+use std::{error, fmt, io};
+
+#[derive(Debug)]
+pub enum RlnError {
+    // reading or writing raw inputs, proofs and keys
+    Serialization(io::Error),
+    // bytes that do not encode a canonical field element
+    InvalidFieldElement(PrimeFieldDecodingError),
+    // inputs built for a tree of another depth than the circuit
+    DepthMismatch { expected: usize, actual: usize },
+    // leaf index that does not fit in the membership tree
+    InvalidLeafIndex(usize),
+    // tree too deep to allocate every node
+    InvalidDepth(usize),
+    // leaf that does not hold the commitment of the given preimage
+    LeafMismatch(usize),
+    // private inputs that do not satisfy the named constraint
+    UnsatisfiedWitness(&'static str),
+    // failure reported by bellman while synthesizing, proving or verifying
+    Synthesis(SynthesisError),
+    // shares from which no member secret can be recovered
+    InvalidShares(&'static str),
+    // epoch that does not map to unix time
+    InvalidEpoch(&'static str),
+    // poseidon hasher called with an unsupported number of inputs
+    InvalidHashArity(usize),
+    // poseidon permutation of an unsupported width
+    InvalidWidth(usize),
+    // secret sharing polynomial of an unsupported degree
+    InvalidDegree(usize),
+    // inputs or proof made for another application
+    RlnIdentifierMismatch,
+    // signal that does not fit the field limbs of the circuit
+    InvalidSignal(&'static str),
+    // hash domain whose length does not fit the length byte
+    InvalidDomain(usize),
+}
+
+impl fmt::Display for RlnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RlnError::Serialization(e) => write!(f, "serialization error: {}", e),
+            RlnError::InvalidFieldElement(e) => write!(f, "invalid field element: {}", e),
+            RlnError::DepthMismatch { expected, actual } => write!(
+                f,
+                "merkle depth mismatch: expected {}, got {}",
+                expected, actual
+            ),
+            RlnError::InvalidLeafIndex(index) => write!(f, "invalid leaf index: {}", index),
+            RlnError::InvalidDepth(depth) => write!(f, "invalid tree depth: {}", depth),
+            RlnError::LeafMismatch(index) => {
+                write!(f, "leaf {} does not match the given preimage", index)
+            }
+            RlnError::UnsatisfiedWitness(constraint) => {
+                write!(f, "unsatisfied witness: {}", constraint)
+            }
+            RlnError::InvalidShares(reason) => write!(f, "invalid shares: {}", reason),
+            RlnError::InvalidEpoch(reason) => write!(f, "invalid epoch: {}", reason),
+            RlnError::Synthesis(e) => write!(f, "synthesis error: {}", e),
+            RlnError::InvalidHashArity(n) => write!(f, "invalid number of hash inputs: {}", n),
+            RlnError::InvalidWidth(t) => write!(f, "unsupported poseidon width: {}", t),
+            RlnError::InvalidDegree(degree) => write!(f, "invalid polynomial degree: {}", degree),
+            RlnError::RlnIdentifierMismatch => write!(f, "rln identifier mismatch"),
+            RlnError::InvalidSignal(reason) => write!(f, "invalid signal: {}", reason),
+            RlnError::InvalidDomain(len) => write!(f, "invalid hash domain length: {}", len),
+        }
+    }
+}
+
+impl error::Error for RlnError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RlnError::Serialization(e) => Some(e),
+            RlnError::InvalidFieldElement(e) => Some(e),
+            RlnError::Synthesis(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RlnError {
+    fn from(e: io::Error) -> Self {
+        RlnError::Serialization(e)
+    }
+}
+
+impl From<PrimeFieldDecodingError> for RlnError {
+    fn from(e: PrimeFieldDecodingError) -> Self {
+        RlnError::InvalidFieldElement(e)
+    }
+}
+
+impl From<SynthesisError> for RlnError {
+    fn from(e: SynthesisError) -> Self {
+        RlnError::Synthesis(e)
+    }
+}
@@ -1,4 +1,6 @@
+#[cfg(feature = "prover")]
 use crate::{circuit::rln, public::RLN};
+use crate::{public::RLNVerifier, utils::read_fr};
 
 use bellman::pairing::bn256::Bn256;
 use std::slice;
@@ -28,14 +30,21 @@ impl<'a> From<&Buffer> for &'a [u8] {
     }
 }
 
+#[cfg(feature = "prover")]
 #[no_mangle]
 pub extern "C" fn new_circuit_from_params(
     merkle_depth: usize,
     parameters_buffer: *const Buffer,
+    rln_identifier_buffer: *const Buffer,
     ctx: *mut *mut RLN<Bn256>,
 ) -> bool {
     let buffer = <&[u8]>::from(unsafe { &*parameters_buffer });
-    let rln = match RLN::<Bn256>::new_with_raw_params(merkle_depth, buffer) {
+    let rln_identifier_data = <&[u8]>::from(unsafe { &*rln_identifier_buffer });
+    let rln_identifier = match read_fr::<_, Bn256>(rln_identifier_data) {
+        Ok(rln_identifier) => rln_identifier,
+        Err(_) => return false,
+    };
+    let rln = match RLN::<Bn256>::new_with_raw_params(merkle_depth, buffer, rln_identifier) {
         Ok(rln) => rln,
         Err(_) => return false,
     };
@@ -43,6 +52,7 @@ pub extern "C" fn new_circuit_from_params(
     true
 }
 
+#[cfg(feature = "prover")]
 #[no_mangle]
 pub extern "C" fn generate_proof(
     ctx: *const RLN<Bn256>,
@@ -52,7 +62,7 @@ pub extern "C" fn generate_proof(
     let rln = unsafe { &*ctx };
     let input_data = <&[u8]>::from(unsafe { &*input_buffer });
     let mut output_data: Vec<u8> = Vec::new();
-    
+
     match rln.generate_proof(input_data, &mut output_data) {
         Ok(proof_data) => proof_data,
         Err(_) => return false,
@@ -62,6 +72,7 @@ pub extern "C" fn generate_proof(
     true
 }
 
+#[cfg(feature = "prover")]
 #[no_mangle]
 pub extern "C" fn verify(
     ctx: *const RLN<Bn256>,
@@ -83,6 +94,30 @@ pub extern "C" fn verify(
     true
 }
 
+#[cfg(feature = "prover")]
+#[no_mangle]
+pub extern "C" fn verify_batch(
+    ctx: *const RLN<Bn256>,
+    proofs_buffer: *const Buffer,
+    proofs_len: usize,
+    result_buffer: *mut Buffer,
+) -> bool {
+    let rln = unsafe { &*ctx };
+    let proofs_data = <&[u8]>::from(unsafe { &*proofs_buffer });
+    let mut output_data: Vec<u8> = Vec::new();
+    match rln.verify_batch_raw(proofs_data, proofs_len, &mut output_data) {
+        Ok(_) => (),
+        Err(_) => return false,
+    };
+    if output_data.is_empty() {
+        return false;
+    }
+    unsafe { *result_buffer = Buffer::from(&output_data[..]) };
+    std::mem::forget(output_data);
+    true
+}
+
+#[cfg(feature = "prover")]
 #[no_mangle]
 pub extern "C" fn hash(
     ctx: *const RLN<Bn256>,
@@ -103,6 +138,26 @@ pub extern "C" fn hash(
     true
 }
 
+#[cfg(feature = "prover")]
+#[no_mangle]
+pub extern "C" fn hash_signal(
+    ctx: *const RLN<Bn256>,
+    signal_buffer: *const Buffer,
+    output_buffer: *mut Buffer,
+) -> bool {
+    let rln = unsafe { &*ctx };
+    let signal_data = <&[u8]>::from(unsafe { &*signal_buffer });
+    let mut output_data: Vec<u8> = Vec::new();
+    match rln.hash_signal(signal_data, &mut output_data) {
+        Ok(_) => (),
+        Err(_) => return false,
+    };
+    unsafe { *output_buffer = Buffer::from(&output_data[..]) };
+    std::mem::forget(output_data);
+    true
+}
+
+#[cfg(feature = "prover")]
 #[no_mangle]
 pub extern "C" fn key_gen(ctx: *const RLN<Bn256>, keypair_buffer: *mut Buffer) -> bool {
     let rln = unsafe { &*ctx };
@@ -116,11 +171,121 @@ pub extern "C" fn key_gen(ctx: *const RLN<Bn256>, keypair_buffer: *mut Buffer) -
     true
 }
 
+#[cfg(feature = "prover")]
+#[no_mangle]
+pub extern "C" fn key_gen_with_seed(
+    ctx: *const RLN<Bn256>,
+    seed_buffer: *const Buffer,
+    keypair_buffer: *mut Buffer,
+) -> bool {
+    let rln = unsafe { &*ctx };
+    let seed_data = <&[u8]>::from(unsafe { &*seed_buffer });
+    let mut output_data: Vec<u8> = Vec::new();
+    match rln.key_gen_with_seed(seed_data, &mut output_data) {
+        Ok(_) => (),
+        Err(_) => return false,
+    }
+    unsafe { *keypair_buffer = Buffer::from(&output_data[..]) };
+    std::mem::forget(output_data);
+    true
+}
+
+#[cfg(feature = "prover")]
+#[no_mangle]
+pub extern "C" fn recover_id_secret(
+    ctx: *const RLN<Bn256>,
+    public_inputs_1_buffer: *const Buffer,
+    public_inputs_2_buffer: *const Buffer,
+    membership_buffer: *const Buffer,
+    secret_buffer: *mut Buffer,
+) -> bool {
+    let rln = unsafe { &*ctx };
+    let public_inputs_1_data = <&[u8]>::from(unsafe { &*public_inputs_1_buffer });
+    let public_inputs_2_data = <&[u8]>::from(unsafe { &*public_inputs_2_buffer });
+    let membership_data = <&[u8]>::from(unsafe { &*membership_buffer });
+    let mut output_data: Vec<u8> = Vec::new();
+    match rln.recover_id_secret_from_public_inputs(
+        public_inputs_1_data,
+        public_inputs_2_data,
+        membership_data,
+        &mut output_data,
+    ) {
+        Ok(_) => (),
+        Err(_) => return false,
+    }
+    unsafe { *secret_buffer = Buffer::from(&output_data[..]) };
+    std::mem::forget(output_data);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn new_verifier_from_key(
+    verifier_key_buffer: *const Buffer,
+    rln_identifier_buffer: *const Buffer,
+    ctx: *mut *mut RLNVerifier<Bn256>,
+) -> bool {
+    let buffer = <&[u8]>::from(unsafe { &*verifier_key_buffer });
+    let rln_identifier_data = <&[u8]>::from(unsafe { &*rln_identifier_buffer });
+    let rln_identifier = match read_fr::<_, Bn256>(rln_identifier_data) {
+        Ok(rln_identifier) => rln_identifier,
+        Err(_) => return false,
+    };
+    let verifier = match RLNVerifier::<Bn256>::new_with_raw_verifier_key(buffer, rln_identifier) {
+        Ok(verifier) => verifier,
+        Err(_) => return false,
+    };
+    unsafe { *ctx = Box::into_raw(Box::new(verifier)) };
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn verifier_verify(
+    ctx: *const RLNVerifier<Bn256>,
+    proof_buffer: *const Buffer,
+    public_inputs_buffer: *const Buffer,
+    result_ptr: *mut u32,
+) -> bool {
+    let verifier = unsafe { &*ctx };
+    let proof_data = <&[u8]>::from(unsafe { &*proof_buffer });
+    let public_inputs_data = <&[u8]>::from(unsafe { &*public_inputs_buffer });
+    if match verifier.verify(proof_data, public_inputs_data) {
+        Ok(verified) => verified,
+        Err(_) => return false,
+    } {
+        unsafe { *result_ptr = 0 };
+    } else {
+        unsafe { *result_ptr = 1 };
+    };
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn verifier_verify_batch(
+    ctx: *const RLNVerifier<Bn256>,
+    proofs_buffer: *const Buffer,
+    proofs_len: usize,
+    result_buffer: *mut Buffer,
+) -> bool {
+    let verifier = unsafe { &*ctx };
+    let proofs_data = <&[u8]>::from(unsafe { &*proofs_buffer });
+    let mut output_data: Vec<u8> = Vec::new();
+    match verifier.verify_batch_raw(proofs_data, proofs_len, &mut output_data) {
+        Ok(_) => (),
+        Err(_) => return false,
+    };
+    if output_data.is_empty() {
+        return false;
+    }
+    unsafe { *result_buffer = Buffer::from(&output_data[..]) };
+    std::mem::forget(output_data);
+    true
+}
+
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
 use std::io::{self, Read, Write};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod tests {
     use crate::circuit::bench;
     use crate::poseidon::PoseidonParams;
@@ -139,15 +304,27 @@ mod tests {
         rln_test
     }
 
+    fn rln_identifier() -> Vec<u8> {
+        let mut rln_identifier: Vec<u8> = Vec::new();
+        bench::RLNTest::<Bn256>::rln_identifier()
+            .into_repr()
+            .write_le(&mut rln_identifier)
+            .unwrap();
+        rln_identifier
+    }
+
     fn rln_pointer(circuit_parameters: Vec<u8>) -> MaybeUninit<*mut RLN<Bn256>> {
         // restore this new curcuit with bindings
         let merkle_depth = merkle_depth();
         let circuit_parameters_buffer = &Buffer::from(circuit_parameters.as_ref());
+        let rln_identifier = rln_identifier();
+        let rln_identifier_buffer = &Buffer::from(rln_identifier.as_ref());
         let mut rln_pointer = MaybeUninit::<*mut RLN<Bn256>>::uninit();
         unsafe {
             new_circuit_from_params(
                 merkle_depth,
                 circuit_parameters_buffer,
+                rln_identifier_buffer,
                 rln_pointer.as_mut_ptr(),
             )
         };
@@ -191,6 +368,127 @@ mod tests {
             unsafe { verify(rln_pointer, &proof_buffer, public_inputs_buffer, result_ptr) };
         assert!(success, "verification operation failed");
         assert_eq!(0, result);
+
+        // a second proof of the same statement must be blinded differently
+        let mut other_proof_buffer = MaybeUninit::<Buffer>::uninit();
+        let success =
+            unsafe { generate_proof(rln_pointer, inputs_buffer, other_proof_buffer.as_mut_ptr()) };
+        assert!(success, "proof generation failed");
+        let other_proof_buffer = unsafe { other_proof_buffer.assume_init() };
+        assert_ne!(
+            <&[u8]>::from(&proof_buffer),
+            <&[u8]>::from(&other_proof_buffer)
+        );
+    }
+
+    #[test]
+    fn test_verify_batch_ffi() {
+        let rln_test = rln_test();
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        rln_test
+            .export_circuit_parameters(&mut circuit_parameters)
+            .unwrap();
+
+        let rln_pointer = rln_pointer(circuit_parameters);
+        let rln_pointer = unsafe { &*rln_pointer.assume_init() };
+
+        let inputs = rln_test.valid_inputs();
+        let mut inputs_data: Vec<u8> = Vec::new();
+        inputs.write(&mut inputs_data).unwrap();
+        let mut public_inputs_data: Vec<u8> = Vec::new();
+        inputs.write_public_inputs(&mut public_inputs_data).unwrap();
+
+        // two valid proofs around one with a wrong share
+        let mut proofs_data: Vec<u8> = Vec::new();
+        for i in 0..3 {
+            rln_pointer
+                .generate_proof(inputs_data.as_slice(), &mut proofs_data)
+                .unwrap();
+            if i == 1 {
+                let mut wrong = public_inputs_data.clone();
+                wrong[64] ^= 1;
+                proofs_data.extend_from_slice(&wrong);
+            } else {
+                proofs_data.extend_from_slice(&public_inputs_data);
+            }
+        }
+        let proofs_buffer = &Buffer::from(proofs_data.as_ref());
+
+        let mut result_buffer = MaybeUninit::<Buffer>::uninit();
+        let success =
+            unsafe { verify_batch(rln_pointer, proofs_buffer, 3, result_buffer.as_mut_ptr()) };
+        assert!(success, "batch verification operation failed");
+        let result_buffer = unsafe { result_buffer.assume_init() };
+        assert_eq!(<&[u8]>::from(&result_buffer), &[1u8, 0, 1][..]);
+    }
+
+    #[test]
+    fn test_verifier_ffi() {
+        let rln_test = rln_test();
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        rln_test
+            .export_circuit_parameters(&mut circuit_parameters)
+            .unwrap();
+        let rln_pointer = rln_pointer(circuit_parameters);
+        let rln_pointer = unsafe { &*rln_pointer.assume_init() };
+
+        let mut verifier_key: Vec<u8> = Vec::new();
+        rln_pointer.export_verifier_key(&mut verifier_key).unwrap();
+        let verifier_key_buffer = &Buffer::from(verifier_key.as_ref());
+        let rln_identifier = rln_identifier();
+        let rln_identifier_buffer = &Buffer::from(rln_identifier.as_ref());
+        let mut verifier_pointer = MaybeUninit::<*mut RLNVerifier<Bn256>>::uninit();
+        let success = unsafe {
+            new_verifier_from_key(
+                verifier_key_buffer,
+                rln_identifier_buffer,
+                verifier_pointer.as_mut_ptr(),
+            )
+        };
+        assert!(success, "verifier construction failed");
+        let verifier_pointer = unsafe { &*verifier_pointer.assume_init() };
+
+        let inputs = rln_test.valid_inputs();
+        let mut inputs_data: Vec<u8> = Vec::new();
+        inputs.write(&mut inputs_data).unwrap();
+        let mut proof_data: Vec<u8> = Vec::new();
+        rln_pointer
+            .generate_proof(inputs_data.as_slice(), &mut proof_data)
+            .unwrap();
+        let mut public_inputs_data: Vec<u8> = Vec::new();
+        inputs.write_public_inputs(&mut public_inputs_data).unwrap();
+
+        let proof_buffer = &Buffer::from(proof_data.as_ref());
+        let public_inputs_buffer = &Buffer::from(public_inputs_data.as_ref());
+        let mut result = 1u32;
+        let success = unsafe {
+            verifier_verify(
+                verifier_pointer,
+                proof_buffer,
+                public_inputs_buffer,
+                &mut result as *mut u32,
+            )
+        };
+        assert!(success, "verification operation failed");
+        assert_eq!(0, result);
+
+        let mut proofs_data = proof_data.clone();
+        proofs_data.extend_from_slice(&public_inputs_data);
+        let proofs_buffer = &Buffer::from(proofs_data.as_ref());
+        let mut result_buffer = MaybeUninit::<Buffer>::uninit();
+        let success = unsafe {
+            verifier_verify_batch(
+                verifier_pointer,
+                proofs_buffer,
+                1,
+                result_buffer.as_mut_ptr(),
+            )
+        };
+        assert!(success, "batch verification operation failed");
+        let result_buffer = unsafe { result_buffer.assume_init() };
+        assert_eq!(<&[u8]>::from(&result_buffer), &[1u8][..]);
     }
 
     #[test]
@@ -201,7 +499,7 @@ mod tests {
         rln_test
             .export_circuit_parameters(&mut circuit_parameters)
             .unwrap();
-        let mut hasher = rln_test.hasher();
+        let hasher = rln_test.hasher();
 
         let rln_pointer = rln_pointer(circuit_parameters);
         let rln_pointer = unsafe { &*rln_pointer.assume_init() };
@@ -220,7 +518,7 @@ mod tests {
         let input_len: usize = 2;
         let input_len_pointer = &input_len as *const usize;
 
-        let expected = hasher.hash(inputs);
+        let expected = hasher.hash(inputs).unwrap();
         let mut expected_data: Vec<u8> = Vec::new();
         expected.into_repr().write_le(&mut expected_data).unwrap();
 
@@ -241,6 +539,36 @@ mod tests {
         assert_eq!(expected_data.as_slice(), result_data);
     }
 
+    #[test]
+    fn test_hash_signal_ffi() {
+        let rln_test = rln_test();
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        rln_test
+            .export_circuit_parameters(&mut circuit_parameters)
+            .unwrap();
+
+        let rln_pointer = rln_pointer(circuit_parameters);
+        let rln_pointer = unsafe { &*rln_pointer.assume_init() };
+
+        let signal = b"hello world";
+        let signal_buffer = &Buffer::from(&signal[..]);
+        let mut result_buffer = MaybeUninit::<Buffer>::uninit();
+        let success =
+            unsafe { hash_signal(rln_pointer, signal_buffer, result_buffer.as_mut_ptr()) };
+        assert!(success, "hash signal ffi call failed");
+
+        let result_buffer = unsafe { result_buffer.assume_init() };
+        let mut result_data = <&[u8]>::from(&result_buffer);
+        let mut buf = <Fr as PrimeField>::Repr::default();
+        buf.read_le(&mut result_data).unwrap();
+        let expected = Fr::from_str(
+            "758578512921340425948370143402390556809730662870189123256287340113097062590",
+        )
+        .unwrap();
+        assert_eq!(Fr::from_repr(buf).unwrap(), expected);
+    }
+
     #[test]
     fn test_keygen_ffi() {
         let rln_test = rln_test();
@@ -249,7 +577,7 @@ mod tests {
         rln_test
             .export_circuit_parameters(&mut circuit_parameters)
             .unwrap();
-        let mut hasher = rln_test.hasher();
+        let hasher = rln_test.hasher();
 
         let rln_pointer = rln_pointer(circuit_parameters);
         let rln_pointer = unsafe { &*rln_pointer.assume_init() };
@@ -267,8 +595,159 @@ mod tests {
         let secret = Fr::from_repr(buf).unwrap();
         buf.read_le(&mut keypair_data).unwrap();
         let public = Fr::from_repr(buf).unwrap();
-        let expected_public: Fr = hasher.hash(vec![secret]);
+        let expected_public: Fr = hasher.hash(vec![secret]).unwrap();
 
         assert_eq!(public, expected_public);
     }
+
+    #[test]
+    fn test_keygen_with_seed_ffi() {
+        let rln_test = rln_test();
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        rln_test
+            .export_circuit_parameters(&mut circuit_parameters)
+            .unwrap();
+
+        let rln_pointer = rln_pointer(circuit_parameters);
+        let rln_pointer = unsafe { &*rln_pointer.assume_init() };
+
+        let keypair = |seed: &[u8]| -> Vec<u8> {
+            let seed_buffer = &Buffer::from(seed);
+            let mut keypair_buffer = MaybeUninit::<Buffer>::uninit();
+            let success =
+                unsafe { key_gen_with_seed(rln_pointer, seed_buffer, keypair_buffer.as_mut_ptr()) };
+            assert!(success, "key generation failed");
+            let keypair_buffer = unsafe { keypair_buffer.assume_init() };
+            <&[u8]>::from(&keypair_buffer).to_vec()
+        };
+
+        assert_eq!(keypair(b"seed 0"), keypair(b"seed 0"));
+        assert_ne!(keypair(b"seed 0"), keypair(b"seed 1"));
+
+        // unseeded key generation must not repeat
+        let mut keypair_buffer_0 = MaybeUninit::<Buffer>::uninit();
+        let mut keypair_buffer_1 = MaybeUninit::<Buffer>::uninit();
+        assert!(unsafe { key_gen(rln_pointer, keypair_buffer_0.as_mut_ptr()) });
+        assert!(unsafe { key_gen(rln_pointer, keypair_buffer_1.as_mut_ptr()) });
+        let keypair_buffer_0 = unsafe { keypair_buffer_0.assume_init() };
+        let keypair_buffer_1 = unsafe { keypair_buffer_1.assume_init() };
+        let keypair_0 = <&[u8]>::from(&keypair_buffer_0);
+        let keypair_1 = <&[u8]>::from(&keypair_buffer_1);
+        assert_ne!(keypair_0, keypair_1);
+    }
+
+    #[test]
+    fn test_recover_id_secret_ffi() {
+        let rln_test = rln_test();
+
+        let mut circuit_parameters: Vec<u8> = Vec::new();
+        rln_test
+            .export_circuit_parameters(&mut circuit_parameters)
+            .unwrap();
+        let hasher = rln_test.hasher();
+
+        let rln_pointer = rln_pointer(circuit_parameters);
+        let rln_pointer = unsafe { &*rln_pointer.assume_init() };
+
+        // same member, same epoch, two different signals
+        let inputs_1 = rln_test.valid_inputs();
+        let mut inputs_2 = inputs_1.clone();
+        let share_x = Fr::from_str("1234").unwrap();
+        let external_nullifier = rln::RLNInputs::<Bn256>::external_nullifier(
+            &hasher,
+            inputs_1.epoch.unwrap(),
+            inputs_1.rln_identifier.unwrap(),
+        )
+        .unwrap();
+        let a_1 = hasher
+            .hash(vec![inputs_1.id_key.unwrap(), external_nullifier])
+            .unwrap();
+        let mut share_y = a_1;
+        share_y.mul_assign(&share_x);
+        share_y.add_assign(&inputs_1.id_key.unwrap());
+        inputs_2.share_x = Some(share_x);
+        inputs_2.share_y = Some(share_y);
+
+        let mut public_inputs_1_data: Vec<u8> = Vec::new();
+        let mut public_inputs_2_data: Vec<u8> = Vec::new();
+        inputs_1
+            .write_public_inputs(&mut public_inputs_1_data)
+            .unwrap();
+        inputs_2
+            .write_public_inputs(&mut public_inputs_2_data)
+            .unwrap();
+        let public_inputs_1_buffer = &Buffer::from(public_inputs_1_data.as_ref());
+        let public_inputs_2_buffer = &Buffer::from(public_inputs_2_data.as_ref());
+
+        // expected root and the path of the member
+        let mut membership_data: Vec<u8> = Vec::new();
+        inputs_1
+            .root
+            .unwrap()
+            .into_repr()
+            .write_le(&mut membership_data)
+            .unwrap();
+        rln::RLNInputs::<Bn256>::encode_auth_path(&mut membership_data, inputs_1.auth_path.clone())
+            .unwrap();
+        let membership_buffer = &Buffer::from(membership_data.as_ref());
+
+        let mut secret_buffer = MaybeUninit::<Buffer>::uninit();
+        let success = unsafe {
+            recover_id_secret(
+                rln_pointer,
+                public_inputs_1_buffer,
+                public_inputs_2_buffer,
+                membership_buffer,
+                secret_buffer.as_mut_ptr(),
+            )
+        };
+        assert!(success, "secret recovery failed");
+        let secret_buffer = unsafe { secret_buffer.assume_init() };
+        let mut secret_data = <&[u8]>::from(&secret_buffer);
+        let mut buf = <Fr as PrimeField>::Repr::default();
+        buf.read_le(&mut secret_data).unwrap();
+        assert_eq!(Fr::from_repr(buf).unwrap(), inputs_1.id_key.unwrap());
+
+        // a single share reveals nothing
+        let mut secret_buffer = MaybeUninit::<Buffer>::uninit();
+        let success = unsafe {
+            recover_id_secret(
+                rln_pointer,
+                public_inputs_1_buffer,
+                public_inputs_1_buffer,
+                membership_buffer,
+                secret_buffer.as_mut_ptr(),
+            )
+        };
+        assert!(!success);
+
+        // shares of a secret that is not in the tree, under the same root
+        let non_member = Fr::from_str("987654321").unwrap();
+        let a_1 = hasher.hash(vec![non_member, external_nullifier]).unwrap();
+        let share = |share_x: Fr| {
+            let mut inputs = inputs_1.clone();
+            let mut share_y = a_1;
+            share_y.mul_assign(&share_x);
+            share_y.add_assign(&non_member);
+            inputs.share_x = Some(share_x);
+            inputs.share_y = Some(share_y);
+            inputs.nullifier = Some(hasher.hash(vec![a_1]).unwrap());
+            let mut data: Vec<u8> = Vec::new();
+            inputs.write_public_inputs(&mut data).unwrap();
+            data
+        };
+        let (non_member_1, non_member_2) = (share(Fr::one()), share(share_x));
+        let mut secret_buffer = MaybeUninit::<Buffer>::uninit();
+        let success = unsafe {
+            recover_id_secret(
+                rln_pointer,
+                &Buffer::from(non_member_1.as_ref()),
+                &Buffer::from(non_member_2.as_ref()),
+                membership_buffer,
+                secret_buffer.as_mut_ptr(),
+            )
+        };
+        assert!(!success);
+    }
 }
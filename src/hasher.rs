@@ -0,0 +1,10 @@
+use crate::error::RlnError;
+
+use sapling_crypto::bellman::pairing::Engine;
+
+// Hash of a few field elements, taken by the membership tree and the
+// native side of the RLN circuits. Implementations are expected to be
+// cheap to clone and to match a HasherGadget for use in circuits.
+pub trait Hasher<E: Engine>: Clone + Send + Sync {
+    fn hash(&self, inputs: Vec<E::Fr>) -> Result<E::Fr, RlnError>;
+}
@@ -0,0 +1,281 @@
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::poseidon::Poseidon as PoseidonHasher;
+use crate::utils::read_fr;
+use rand::{Rand, Rng};
+use sapling_crypto::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use std::io::{Read, Write};
+
+// Semaphore compatible identity.
+// secret = H(nullifier, trapdoor) and commitment = H(secret),
+// the secret is the id_key of RLN so that the commitment registered
+// in a Semaphore group is also the RLN membership leaf.
+// H is the hasher of the membership tree.
+
+#[derive(Clone)]
+pub struct Identity<E, H = PoseidonHasher<E>>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    trapdoor: E::Fr,
+    nullifier: E::Fr,
+    hasher: H,
+}
+
+impl<E> Identity<E>
+where
+    E: Engine,
+{
+    pub fn new(trapdoor: E::Fr, nullifier: E::Fr) -> Identity<E> {
+        Self::new_with_hasher(trapdoor, nullifier, PoseidonHasher::new())
+    }
+
+    pub fn random<G: Rng>(rng: &mut G) -> Identity<E> {
+        Self::random_with_hasher(rng, PoseidonHasher::new())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<Identity<E>, RlnError> {
+        Self::read_with_hasher(reader, PoseidonHasher::new())
+    }
+}
+
+impl<E, H> Identity<E, H>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    pub fn new_with_hasher(trapdoor: E::Fr, nullifier: E::Fr, hasher: H) -> Identity<E, H> {
+        Identity {
+            trapdoor,
+            nullifier,
+            hasher,
+        }
+    }
+
+    pub fn random_with_hasher<G: Rng>(rng: &mut G, hasher: H) -> Identity<E, H> {
+        let trapdoor = E::Fr::rand(rng);
+        let nullifier = E::Fr::rand(rng);
+        Identity::new_with_hasher(trapdoor, nullifier, hasher)
+    }
+
+    pub fn trapdoor(&self) -> E::Fr {
+        self.trapdoor
+    }
+
+    pub fn nullifier(&self) -> E::Fr {
+        self.nullifier
+    }
+
+    /// The RLN id_key of the identity.
+    pub fn secret(&self) -> Result<E::Fr, RlnError> {
+        self.hasher.hash(vec![self.nullifier, self.trapdoor])
+    }
+
+    /// The group commitment, equal to the RLN leaf `H(secret)`.
+    pub fn commitment(&self) -> Result<E::Fr, RlnError> {
+        let secret = self.secret()?;
+        self.hasher.hash(vec![secret])
+    }
+
+    // trapdoor followed by nullifier
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        self.trapdoor.into_repr().write_le(&mut writer)?;
+        self.nullifier.into_repr().write_le(&mut writer)?;
+        Ok(())
+    }
+
+    pub fn read_with_hasher<R: Read>(mut reader: R, hasher: H) -> Result<Identity<E, H>, RlnError> {
+        let trapdoor = read_fr::<_, E>(&mut reader)?;
+        let nullifier = read_fr::<_, E>(&mut reader)?;
+        Ok(Identity::new_with_hasher(trapdoor, nullifier, hasher))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Identity;
+    use crate::circuit::poseidon::PoseidonCircuit;
+    use crate::circuit::rln::{RLNCircuit, RLNIdentityCircuit};
+    use crate::merkle::MerkleTree;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use crate::public::RLN;
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+    use sapling_crypto::bellman::Circuit;
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+
+    #[test]
+    fn test_identity_is_rln_member() {
+        let merkle_depth = 3;
+        let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let identity = Identity::<Bn256>::random(&mut rng);
+        let mut raw_identity: Vec<u8> = Vec::new();
+        identity.write(&mut raw_identity).unwrap();
+        let restored = Identity::<Bn256>::read(raw_identity.as_slice()).unwrap();
+        assert_eq!(restored.trapdoor(), identity.trapdoor());
+        assert_eq!(restored.nullifier(), identity.nullifier());
+
+        // the commitment registered for the group is the leaf of the secret
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let commitment = identity.commitment().unwrap();
+        assert_eq!(
+            commitment,
+            hasher.hash(vec![identity.secret().unwrap()]).unwrap()
+        );
+        let mut membership_tree = MerkleTree::empty(hasher, merkle_depth).unwrap();
+        membership_tree.update(4, commitment).unwrap();
+
+        // plain RLN proofs with the secret as id_key
+        let rln_identifier = Fr::from_str("100").unwrap();
+        let rln = RLN::<Bn256>::new(merkle_depth, rln_identifier).unwrap();
+        let epoch = Fr::rand(&mut rng);
+        let message = rln
+            .prove_signal(
+                identity.secret().unwrap(),
+                &membership_tree,
+                4,
+                epoch,
+                b"signal",
+            )
+            .unwrap();
+        assert!(rln.verify_signal(&message, b"signal").unwrap());
+
+        // proofs over the trapdoor and nullifier give the same public inputs
+        let circuit = RLNIdentityCircuit::from_identity(
+            &identity,
+            &membership_tree,
+            4,
+            epoch,
+            rln_identifier,
+            message.share_x,
+        )
+        .unwrap();
+        circuit.check_witness().unwrap();
+        assert_eq!(
+            circuit.inputs.public_inputs().unwrap(),
+            message.public_inputs()
+        );
+
+        let parameters =
+            generate_random_parameters(RLNIdentityCircuit::<Bn256>::empty(merkle_depth), &mut rng)
+                .unwrap();
+        let proof = create_random_proof(circuit.clone(), &parameters, &mut rng).unwrap();
+        let verifying_key = prepare_verifying_key(&parameters.vk);
+        assert!(verify_proof(&verifying_key, &proof, &message.public_inputs()).unwrap());
+    }
+
+    #[test]
+    fn test_identity_circuit() {
+        let merkle_depth = 3;
+        let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let identity = Identity::<Bn256>::random(&mut rng);
+        let mut membership_tree =
+            MerkleTree::empty(PoseidonHasher::<Bn256>::new(), merkle_depth).unwrap();
+        membership_tree
+            .update(1, identity.commitment().unwrap())
+            .unwrap();
+
+        let circuit = RLNIdentityCircuit::from_identity(
+            &identity,
+            &membership_tree,
+            1,
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+        )
+        .unwrap();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.clone().synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(cs.find_unconstrained().is_empty());
+
+        // RLNCircuit accepts the same inputs over the secret alone
+        let mut plain = TestConstraintSystem::<Bn256>::new();
+        RLNCircuit::<Bn256> {
+            inputs: circuit.inputs.clone(),
+            hasher: PoseidonCircuit::new(),
+        }
+        .synthesize(&mut plain)
+        .unwrap();
+        assert!(plain.is_satisfied());
+        assert!(cs.num_constraints() > plain.num_constraints());
+
+        let mut wrong_trapdoor = circuit.clone();
+        wrong_trapdoor
+            .identity_trapdoor
+            .as_mut()
+            .unwrap()
+            .add_assign(&Fr::one());
+        assert!(wrong_trapdoor.check_witness().is_err());
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        wrong_trapdoor.synthesize(&mut cs).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_identity_hasher() {
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        use crate::poseidon2::Poseidon2;
+
+        let merkle_depth = 3;
+        let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let hasher = Poseidon2::<Bn256>::new();
+        let identity = Identity::<Bn256, _>::random_with_hasher(&mut rng, hasher.clone());
+        let poseidon_identity = Identity::<Bn256>::new(identity.trapdoor(), identity.nullifier());
+        assert_ne!(
+            identity.secret().unwrap(),
+            poseidon_identity.secret().unwrap()
+        );
+        assert_eq!(
+            identity.commitment().unwrap(),
+            hasher.hash(vec![identity.secret().unwrap()]).unwrap()
+        );
+
+        let mut raw_identity: Vec<u8> = Vec::new();
+        identity.write(&mut raw_identity).unwrap();
+        let restored =
+            Identity::<Bn256, _>::read_with_hasher(raw_identity.as_slice(), hasher.clone())
+                .unwrap();
+        assert_eq!(
+            restored.commitment().unwrap(),
+            identity.commitment().unwrap()
+        );
+
+        let mut membership_tree = MerkleTree::empty(hasher, merkle_depth).unwrap();
+        membership_tree
+            .update(2, identity.commitment().unwrap())
+            .unwrap();
+        let circuit = RLNIdentityCircuit::from_identity_with_hasher(
+            &identity,
+            &membership_tree,
+            2,
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+            Poseidon2Circuit::<Bn256>::new(),
+        )
+        .unwrap();
+        circuit.check_witness().unwrap();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.clone().synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+
+        // the poseidon circuit does not open the poseidon2 identity
+        let poseidon_circuit = RLNIdentityCircuit::<Bn256> {
+            inputs: circuit.inputs.clone(),
+            identity_nullifier: circuit.identity_nullifier,
+            identity_trapdoor: circuit.identity_trapdoor,
+            hasher: PoseidonCircuit::new(),
+        };
+        assert!(poseidon_circuit.check_witness().is_err());
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        poseidon_circuit.synthesize(&mut cs).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+}
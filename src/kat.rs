@@ -0,0 +1,238 @@
+// Known answer tests against vectors/kat.json, Poseidon outputs of circomlibjs,
+// zk-kit tree roots and RLN v1 and v2 witnesses computed by zerokit.
+
+use crate::circuit::poseidon::PoseidonCircuit;
+use crate::circuit::rln::{RLNCircuit, RLNInputs};
+use crate::circuit::rln_v2::{RLNV2Circuit, RLNV2Inputs};
+use crate::merkle::{DenseMerkleTree, MerkleTree};
+use crate::poseidon::Poseidon;
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+use sapling_crypto::bellman::pairing::ff::PrimeField;
+use sapling_crypto::bellman::{Circuit, ConstraintSystem};
+use sapling_crypto::circuit::num::AllocatedNum;
+use sapling_crypto::circuit::test::TestConstraintSystem;
+use serde_json::Value;
+
+fn vectors(section: &str) -> Vec<Value> {
+    let vectors: Value = serde_json::from_str(include_str!("../vectors/kat.json")).unwrap();
+    vectors[section].as_array().unwrap().clone()
+}
+
+fn fr(v: &Value) -> Fr {
+    Fr::from_str(v.as_str().unwrap()).unwrap()
+}
+
+fn index(v: &Value) -> usize {
+    v.as_u64().unwrap() as usize
+}
+
+#[test]
+fn test_poseidon_vectors() {
+    let hasher = Poseidon::<Bn256>::new();
+    for v in vectors("poseidon").iter() {
+        let inputs: Vec<Fr> = v["inputs"].as_array().unwrap().iter().map(fr).collect();
+        let output = fr(&v["output"]);
+        assert_eq!(hasher.hash(inputs.clone()).unwrap(), output, "{}", v);
+
+        // the circuit must agree for proofs to interoperate
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let allocated = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*e)).unwrap()
+            })
+            .collect();
+        let digest = PoseidonCircuit::<Bn256>::new()
+            .alloc(cs.namespace(|| "hash"), allocated)
+            .unwrap();
+        assert_eq!(digest.get_value(), Some(output));
+        assert!(cs.is_satisfied());
+    }
+}
+
+#[test]
+fn test_merkle_vectors() {
+    for v in vectors("merkle").iter() {
+        let depth = index(&v["depth"]);
+        let mut tree = MerkleTree::empty(Poseidon::<Bn256>::new(), depth).unwrap();
+        let mut dense = DenseMerkleTree::empty(Poseidon::<Bn256>::new(), depth).unwrap();
+        for leaf in v["leaves"].as_array().unwrap().iter() {
+            tree.update(index(&leaf["index"]), fr(&leaf["value"]))
+                .unwrap();
+            dense
+                .update(index(&leaf["index"]), fr(&leaf["value"]))
+                .unwrap();
+        }
+        assert_eq!(tree.root(), fr(&v["root"]), "{}", v);
+        assert_eq!(dense.root(), fr(&v["root"]), "{}", v);
+    }
+}
+
+// authentication path of leaf_index from the path elements of zerokit,
+// ordered from the leaf
+fn auth_path(v: &Value) -> Vec<Option<(Fr, bool)>> {
+    let leaf_index = index(&v["leaf_index"]);
+    v["path_elements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(level, e)| Some((fr(e), (leaf_index >> level) & 1 == 0)))
+        .collect()
+}
+
+// public inputs in the order of the circuits
+fn public_inputs(v: &Value) -> Vec<Fr> {
+    [
+        "root",
+        "epoch",
+        "share_x",
+        "share_y",
+        "nullifier",
+        "rln_identifier",
+    ]
+    .iter()
+    .map(|k| fr(&v[*k]))
+    .collect()
+}
+
+#[test]
+fn test_rln_vectors() {
+    let hasher = Poseidon::<Bn256>::new();
+    for v in vectors("rln").iter() {
+        let id_key = fr(&v["id_key"]);
+        let leaf_index = index(&v["leaf_index"]);
+        let (epoch, rln_identifier) = (fr(&v["epoch"]), fr(&v["rln_identifier"]));
+        let expected = public_inputs(v);
+
+        let id_commitment = hasher.hash(vec![id_key]).unwrap();
+        assert_eq!(id_commitment, fr(&v["id_commitment"]), "{}", v["source"]);
+        let external_nullifier =
+            RLNInputs::<Bn256>::external_nullifier(&hasher, epoch, rln_identifier).unwrap();
+        assert_eq!(external_nullifier, fr(&v["external_nullifier"]));
+
+        // the witness of zerokit is a witness here
+        let inputs = RLNInputs::<Bn256> {
+            share_x: Some(fr(&v["share_x"])),
+            share_y: Some(fr(&v["share_y"])),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(fr(&v["nullifier"])),
+            root: Some(fr(&v["root"])),
+            id_key: Some(id_key),
+            auth_path: auth_path(v),
+        };
+        inputs.check_witness(&hasher).unwrap();
+        assert_eq!(inputs.public_inputs().unwrap(), expected);
+
+        // and the one built here from a tree holding only the member
+        if v["only_leaf"].as_bool().unwrap() {
+            let mut tree = MerkleTree::empty(hasher.clone(), index(&v["depth"])).unwrap();
+            tree.update(leaf_index, id_commitment).unwrap();
+            let mut dense = DenseMerkleTree::empty(hasher.clone(), index(&v["depth"])).unwrap();
+            dense.update(leaf_index, id_commitment).unwrap();
+            for built in [
+                RLNInputs::from_identity(
+                    id_key,
+                    &tree,
+                    leaf_index,
+                    epoch,
+                    rln_identifier,
+                    fr(&v["share_x"]),
+                ),
+                RLNInputs::from_identity(
+                    id_key,
+                    &dense,
+                    leaf_index,
+                    epoch,
+                    rln_identifier,
+                    fr(&v["share_x"]),
+                ),
+            ]
+            .iter()
+            {
+                let built = built.as_ref().unwrap();
+                assert_eq!(built.public_inputs().unwrap(), expected);
+                assert_eq!(built.auth_path, inputs.auth_path);
+            }
+        }
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNCircuit {
+            inputs,
+            hasher: PoseidonCircuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&expected));
+    }
+}
+
+#[test]
+fn test_rln_v2_vectors() {
+    let hasher = Poseidon::<Bn256>::new();
+    for v in vectors("rln_v2").iter() {
+        let id_key = fr(&v["id_key"]);
+        let leaf_index = index(&v["leaf_index"]);
+        let (epoch, rln_identifier) = (fr(&v["epoch"]), fr(&v["rln_identifier"]));
+        let (user_message_limit, message_id) = (fr(&v["user_message_limit"]), fr(&v["message_id"]));
+        let expected = public_inputs(v);
+
+        assert_eq!(
+            hasher.hash(vec![id_key]).unwrap(),
+            fr(&v["id_commitment"]),
+            "{}",
+            v["source"]
+        );
+        let rate_commitment =
+            RLNV2Inputs::<Bn256>::rate_commitment(&hasher, id_key, user_message_limit).unwrap();
+        assert_eq!(rate_commitment, fr(&v["rate_commitment"]));
+        assert_eq!(
+            RLNInputs::<Bn256>::external_nullifier(&hasher, epoch, rln_identifier).unwrap(),
+            fr(&v["external_nullifier"])
+        );
+
+        let inputs = RLNV2Inputs::<Bn256> {
+            share_x: Some(fr(&v["share_x"])),
+            share_y: Some(fr(&v["share_y"])),
+            epoch: Some(epoch),
+            rln_identifier: Some(rln_identifier),
+            nullifier: Some(fr(&v["nullifier"])),
+            root: Some(fr(&v["root"])),
+            id_key: Some(id_key),
+            user_message_limit: Some(user_message_limit),
+            message_id: Some(message_id),
+            auth_path: auth_path(v),
+        };
+        inputs.check_witness(&hasher).unwrap();
+        assert_eq!(inputs.public_inputs().unwrap(), expected);
+
+        if v["only_leaf"].as_bool().unwrap() {
+            let mut tree = MerkleTree::empty(hasher.clone(), index(&v["depth"])).unwrap();
+            tree.update(leaf_index, rate_commitment).unwrap();
+            let built = RLNV2Inputs::from_identity(
+                id_key,
+                user_message_limit,
+                message_id,
+                &tree,
+                leaf_index,
+                epoch,
+                rln_identifier,
+                fr(&v["share_x"]),
+            )
+            .unwrap();
+            assert_eq!(built.public_inputs().unwrap(), expected);
+            assert_eq!(built.auth_path, inputs.auth_path);
+        }
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = RLNV2Circuit {
+            inputs,
+            hasher: PoseidonCircuit::new(),
+        };
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&expected));
+    }
+}
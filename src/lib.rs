@@ -1,14 +1,32 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
+mod batch;
+#[cfg(feature = "prover")]
 pub mod circuit;
+pub mod epoch;
+pub mod error;
+#[cfg(feature = "prover")]
+pub mod hasher;
+#[cfg(feature = "prover")]
+pub mod identity;
+#[cfg(feature = "prover")]
 pub mod merkle;
+#[cfg(feature = "prover")]
+pub mod nullifier_log;
+#[cfg(feature = "prover")]
 pub mod poseidon;
+#[cfg(feature = "prover")]
+pub mod poseidon2;
 pub mod public;
+#[cfg(feature = "prover")]
 mod poseidon_utils;
 
 mod utils;
 
+#[cfg(all(test, feature = "prover"))]
+mod kat;
+
 #[cfg(not(target_arch = "wasm32"))]
 pub mod ffi;
 
@@ -1,35 +1,118 @@
-use crate::poseidon::{Poseidon as Hasher, PoseidonParams};
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::poseidon::{Poseidon, PoseidonParams};
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
 use std::collections::HashMap;
+use std::mem;
 
-pub struct MerkleTree<E>
+// Operations shared by the sparse and the dense trees, so that witnesses
+// can be built from either. Leaves are hashes of the member preimages.
+pub trait MembershipTree<E: Engine> {
+    type Hasher: Hasher<E>;
+
+    fn hasher(&self) -> &Self::Hasher;
+
+    fn depth(&self) -> usize;
+
+    // sets leaf_index to the hash of new, old must be the current preimage if given
+    fn insert(&mut self, leaf_index: usize, new: E::Fr, old: Option<E::Fr>)
+        -> Result<(), RlnError>;
+
+    fn update(&mut self, leaf_index: usize, leaf: E::Fr) -> Result<(), RlnError>;
+
+    fn root(&self) -> E::Fr;
+
+    // lowest index holding the given non-zero leaf, empty leaves are
+    // zero and never found
+    fn leaf_index(&self, leaf: E::Fr) -> Option<usize>;
+
+    fn witness(&self, leaf_index: usize) -> Result<Vec<(E::Fr, bool)>, RlnError>;
+
+    fn check_inclusion(
+        &self,
+        witness: Vec<(E::Fr, bool)>,
+        leaf_index: usize,
+        data: E::Fr,
+    ) -> Result<bool, RlnError>;
+}
+
+// zero nodes of a tree, from the root to the leaves
+fn zero_nodes<E: Engine, H: Hasher<E>>(hasher: &H, depth: usize) -> Result<Vec<E::Fr>, RlnError> {
+    let mut zero: Vec<E::Fr> = Vec::with_capacity(depth + 1);
+    zero.push(E::Fr::from_str("0").unwrap());
+    for i in 0..depth {
+        zero.push(hasher.hash([zero[i]; 2].to_vec())?);
+    }
+    zero.reverse();
+    Ok(zero)
+}
+
+fn check_leaf_index(depth: usize, leaf_index: usize) -> Result<(), RlnError> {
+    match leaf_index.checked_shr(depth as u32) {
+        Some(0) | None => Ok(()),
+        Some(_) => Err(RlnError::InvalidLeafIndex(leaf_index)),
+    }
+}
+
+// an empty leaf matches the zero preimage
+fn leaf_matches<E: Engine, H: Hasher<E>>(
+    hasher: &H,
+    leaf: E::Fr,
+    preimage: E::Fr,
+) -> Result<bool, RlnError> {
+    if leaf.is_zero() {
+        Ok(preimage.is_zero())
+    } else {
+        Ok(leaf == hasher.hash(vec![preimage])?)
+    }
+}
+
+pub(crate) fn witness_root<E: Engine, H: Hasher<E>>(
+    hasher: &H,
+    leaf: E::Fr,
+    witness: Vec<(E::Fr, bool)>,
+) -> Result<E::Fr, RlnError> {
+    let mut acc = leaf;
+    for w in witness.into_iter() {
+        if w.1 {
+            acc = hasher.hash(vec![acc, w.0])?;
+        } else {
+            acc = hasher.hash(vec![w.0, acc])?;
+        }
+    }
+    Ok(acc)
+}
+
+// binary tree of the given hasher, Poseidon unless chosen otherwise
+pub struct MerkleTree<E, H = Poseidon<E>>
 where
     E: Engine,
+    H: Hasher<E>,
 {
-    pub hasher: Hasher<E>,
+    pub hasher: H,
     zero: Vec<E::Fr>,
     depth: usize,
     nodes: HashMap<(usize, usize), E::Fr>,
 }
 
-impl<E> MerkleTree<E>
+impl<E, H> MerkleTree<E, H>
 where
     E: Engine,
+    H: Hasher<E>,
 {
-    pub fn empty(mut hasher: Hasher<E>, depth: usize) -> Self {
-        let mut zero: Vec<E::Fr> = Vec::with_capacity(depth + 1);
-        zero.push(E::Fr::from_str("0").unwrap());
-        for i in 0..depth {
-            zero.push(hasher.hash([zero[i]; 2].to_vec()));
-        }
-        zero.reverse();
-        MerkleTree {
-            hasher: hasher,
-            zero: zero.clone(),
-            depth: depth,
+    pub fn empty(hasher: H, depth: usize) -> Result<Self, RlnError> {
+        let zero = zero_nodes(&hasher, depth)?;
+        Ok(MerkleTree {
+            hasher,
+            zero,
+            depth,
             nodes: HashMap::new(),
-        }
+        })
+    }
+
+    fn check_leaf_index(&self, leaf_index: usize) -> Result<(), RlnError> {
+        check_leaf_index(self.depth, leaf_index)
     }
 
     fn get_node(&self, depth: usize, index: usize) -> E::Fr {
@@ -39,89 +122,313 @@ where
             .unwrap_or_else(|| &self.zero[depth])
     }
 
-    fn hash_couple(&mut self, depth: usize, index: usize) -> E::Fr {
+    fn hash_couple(&self, depth: usize, index: usize) -> Result<E::Fr, RlnError> {
         let b = index & !1;
         self.hasher
             .hash([self.get_node(depth, b), self.get_node(depth, b + 1)].to_vec())
     }
 
-    fn recalculate_from(&mut self, leaf_index: usize) {
+    fn recalculate_from(&mut self, leaf_index: usize) -> Result<(), RlnError> {
         let mut i = leaf_index;
         let mut depth = self.depth;
-        loop {
-            let h = self.hash_couple(depth, i);
+        while depth > 0 {
+            let h = self.hash_couple(depth, i)?;
             i >>= 1;
             depth -= 1;
             self.nodes.insert((depth, i), h);
-            if depth == 0 {
-                break;
-            }
         }
-        assert_eq!(depth, 0);
-        assert_eq!(i, 0);
+        Ok(())
     }
 
-    pub fn insert(&mut self, leaf_index: usize, new: E::Fr, old: Option<E::Fr>) {
+    pub fn insert(
+        &mut self,
+        leaf_index: usize,
+        new: E::Fr,
+        old: Option<E::Fr>,
+    ) -> Result<(), RlnError> {
+        self.check_leaf_index(leaf_index)?;
         let d = self.depth;
-        {
-            if old.is_some() {
-                let old = old.unwrap();
-                let t = self.get_node(d, leaf_index);
-                if t.is_zero() {
-                    assert!(old.is_zero());
-                } else {
-                    assert!(t.eq(&self.hasher.hash(vec![old])));
-                }
+        if let Some(old) = old {
+            if !leaf_matches(&self.hasher, self.get_node(d, leaf_index), old)? {
+                return Err(RlnError::LeafMismatch(leaf_index));
             }
-        };
-        let leaf = self.hasher.hash(vec![new]);
-        self.update(leaf_index, leaf);
+        }
+        let leaf = self.hasher.hash(vec![new])?;
+        self.update(leaf_index, leaf)
     }
 
-    pub fn update(&mut self, leaf_index: usize, leaf: E::Fr) {
+    pub fn update(&mut self, leaf_index: usize, leaf: E::Fr) -> Result<(), RlnError> {
+        self.check_leaf_index(leaf_index)?;
         self.nodes.insert((self.depth, leaf_index), leaf);
-        self.recalculate_from(leaf_index);
+        self.recalculate_from(leaf_index)
     }
 
     pub fn root(&self) -> E::Fr {
         return self.get_node(0, 0);
     }
 
-    pub fn witness(&mut self, leaf_index: usize) -> Vec<(E::Fr, bool)> {
+    pub fn leaf_index(&self, leaf: E::Fr) -> Option<usize> {
+        if leaf.is_zero() {
+            return None;
+        }
+        self.nodes
+            .iter()
+            .filter(|((depth, _), node)| *depth == self.depth && **node == leaf)
+            .map(|((_, index), _)| *index)
+            .min()
+    }
+
+    pub fn witness(&self, leaf_index: usize) -> Result<Vec<(E::Fr, bool)>, RlnError> {
+        self.check_leaf_index(leaf_index)?;
         let mut witness = Vec::<(E::Fr, bool)>::with_capacity(self.depth);
         let mut i = leaf_index;
         let mut depth = self.depth;
-        loop {
+        while depth > 0 {
             i ^= 1;
             witness.push((self.get_node(depth, i), (i & 1 == 1)));
             i >>= 1;
             depth -= 1;
-            if depth == 0 {
-                break;
-            }
         }
-        assert_eq!(i, 0);
-        witness
+        Ok(witness)
     }
 
     pub fn check_inclusion(
+        &self,
+        witness: Vec<(E::Fr, bool)>,
+        leaf_index: usize,
+        data: E::Fr,
+    ) -> Result<bool, RlnError> {
+        self.check_leaf_index(leaf_index)?;
+        let leaf = self.hasher.hash(vec![data])?;
+        if !self.get_node(self.depth, leaf_index).eq(&leaf) {
+            return Ok(false);
+        }
+        Ok(witness_root(&self.hasher, leaf, witness)?.eq(&self.root()))
+    }
+
+    // approximate heap bytes of the stored nodes
+    pub(crate) fn memory(&self) -> usize {
+        let entry = mem::size_of::<((usize, usize), E::Fr)>() + 1;
+        self.nodes.capacity() * entry + self.zero.capacity() * mem::size_of::<E::Fr>()
+    }
+}
+
+impl<E, H> MembershipTree<E> for MerkleTree<E, H>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    type Hasher = H;
+
+    fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn insert(
         &mut self,
+        leaf_index: usize,
+        new: E::Fr,
+        old: Option<E::Fr>,
+    ) -> Result<(), RlnError> {
+        MerkleTree::insert(self, leaf_index, new, old)
+    }
+
+    fn update(&mut self, leaf_index: usize, leaf: E::Fr) -> Result<(), RlnError> {
+        MerkleTree::update(self, leaf_index, leaf)
+    }
+
+    fn root(&self) -> E::Fr {
+        MerkleTree::root(self)
+    }
+
+    fn leaf_index(&self, leaf: E::Fr) -> Option<usize> {
+        MerkleTree::leaf_index(self, leaf)
+    }
+
+    fn witness(&self, leaf_index: usize) -> Result<Vec<(E::Fr, bool)>, RlnError> {
+        MerkleTree::witness(self, leaf_index)
+    }
+
+    fn check_inclusion(
+        &self,
         witness: Vec<(E::Fr, bool)>,
         leaf_index: usize,
         data: E::Fr,
-    ) -> bool {
-        let mut acc = self.hasher.hash(vec![data]);
-        {
-            assert!(self.get_node(self.depth, leaf_index).eq(&acc));
+    ) -> Result<bool, RlnError> {
+        MerkleTree::check_inclusion(self, witness, leaf_index, data)
+    }
+}
+
+// Full tree in one array, level by level from the root with node i of
+// level d at 2^d - 1 + i. Every node is allocated up front, which for a
+// mostly populated tree takes less memory than the map of MerkleTree
+// and avoids hashing node keys on each update.
+pub struct DenseMerkleTree<E, H = Poseidon<E>>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    pub hasher: H,
+    depth: usize,
+    nodes: Vec<E::Fr>,
+}
+
+impl<E, H> DenseMerkleTree<E, H>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    pub fn empty(hasher: H, depth: usize) -> Result<Self, RlnError> {
+        if depth + 1 >= usize::BITS as usize {
+            return Err(RlnError::InvalidDepth(depth));
+        }
+        let mut nodes: Vec<E::Fr> = Vec::new();
+        nodes
+            .try_reserve_exact((1 << (depth + 1)) - 1)
+            .map_err(|_| RlnError::InvalidDepth(depth))?;
+        for (d, zero) in zero_nodes(&hasher, depth)?.into_iter().enumerate() {
+            nodes.resize(nodes.len() + (1 << d), zero);
         }
-        for w in witness.into_iter() {
-            if w.1 {
-                acc = self.hasher.hash(vec![acc, w.0]);
-            } else {
-                acc = self.hasher.hash(vec![w.0, acc]);
+        Ok(DenseMerkleTree {
+            hasher,
+            depth,
+            nodes,
+        })
+    }
+
+    fn node_index(depth: usize, index: usize) -> usize {
+        (1 << depth) - 1 + index
+    }
+
+    fn get_node(&self, depth: usize, index: usize) -> E::Fr {
+        self.nodes[Self::node_index(depth, index)]
+    }
+
+    pub fn insert(
+        &mut self,
+        leaf_index: usize,
+        new: E::Fr,
+        old: Option<E::Fr>,
+    ) -> Result<(), RlnError> {
+        check_leaf_index(self.depth, leaf_index)?;
+        if let Some(old) = old {
+            if !leaf_matches(&self.hasher, self.get_node(self.depth, leaf_index), old)? {
+                return Err(RlnError::LeafMismatch(leaf_index));
             }
         }
-        acc.eq(&self.root())
+        let leaf = self.hasher.hash(vec![new])?;
+        self.update(leaf_index, leaf)
+    }
+
+    pub fn update(&mut self, leaf_index: usize, leaf: E::Fr) -> Result<(), RlnError> {
+        check_leaf_index(self.depth, leaf_index)?;
+        self.nodes[Self::node_index(self.depth, leaf_index)] = leaf;
+        let mut i = leaf_index;
+        for depth in (1..=self.depth).rev() {
+            let b = Self::node_index(depth, i & !1);
+            let h = self.hasher.hash(vec![self.nodes[b], self.nodes[b + 1]])?;
+            i >>= 1;
+            self.nodes[Self::node_index(depth - 1, i)] = h;
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> E::Fr {
+        self.nodes[0]
+    }
+
+    pub fn leaf_index(&self, leaf: E::Fr) -> Option<usize> {
+        if leaf.is_zero() {
+            return None;
+        }
+        self.nodes[Self::node_index(self.depth, 0)..]
+            .iter()
+            .position(|node| *node == leaf)
+    }
+
+    pub fn witness(&self, leaf_index: usize) -> Result<Vec<(E::Fr, bool)>, RlnError> {
+        check_leaf_index(self.depth, leaf_index)?;
+        let mut witness = Vec::<(E::Fr, bool)>::with_capacity(self.depth);
+        let mut i = leaf_index;
+        for depth in (1..=self.depth).rev() {
+            i ^= 1;
+            witness.push((self.get_node(depth, i), (i & 1 == 1)));
+            i >>= 1;
+        }
+        Ok(witness)
+    }
+
+    pub fn check_inclusion(
+        &self,
+        witness: Vec<(E::Fr, bool)>,
+        leaf_index: usize,
+        data: E::Fr,
+    ) -> Result<bool, RlnError> {
+        check_leaf_index(self.depth, leaf_index)?;
+        let leaf = self.hasher.hash(vec![data])?;
+        if self.get_node(self.depth, leaf_index) != leaf {
+            return Ok(false);
+        }
+        Ok(witness_root(&self.hasher, leaf, witness)? == self.root())
+    }
+
+    // approximate heap bytes of the stored nodes
+    pub(crate) fn memory(&self) -> usize {
+        self.nodes.capacity() * mem::size_of::<E::Fr>()
+    }
+}
+
+impl<E, H> MembershipTree<E> for DenseMerkleTree<E, H>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    type Hasher = H;
+
+    fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn insert(
+        &mut self,
+        leaf_index: usize,
+        new: E::Fr,
+        old: Option<E::Fr>,
+    ) -> Result<(), RlnError> {
+        DenseMerkleTree::insert(self, leaf_index, new, old)
+    }
+
+    fn update(&mut self, leaf_index: usize, leaf: E::Fr) -> Result<(), RlnError> {
+        DenseMerkleTree::update(self, leaf_index, leaf)
+    }
+
+    fn root(&self) -> E::Fr {
+        DenseMerkleTree::root(self)
+    }
+
+    fn leaf_index(&self, leaf: E::Fr) -> Option<usize> {
+        DenseMerkleTree::leaf_index(self, leaf)
+    }
+
+    fn witness(&self, leaf_index: usize) -> Result<Vec<(E::Fr, bool)>, RlnError> {
+        DenseMerkleTree::witness(self, leaf_index)
+    }
+
+    fn check_inclusion(
+        &self,
+        witness: Vec<(E::Fr, bool)>,
+        leaf_index: usize,
+        data: E::Fr,
+    ) -> Result<bool, RlnError> {
+        DenseMerkleTree::check_inclusion(self, witness, leaf_index, data)
     }
 }
 
@@ -132,21 +439,116 @@ fn test_merkle_set() {
         .map(|s| Fr::from_str(&format!("{}", s)).unwrap())
         .collect();
     use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr, FrRepr};
-    let hasher = Hasher::<Bn256>::new();
-    let mut set = MerkleTree::empty(hasher, 3);
+    let hasher = Poseidon::<Bn256>::new();
+    let mut set = MerkleTree::empty(hasher, 3).unwrap();
     let leaf_index = 6;
-    set.insert(leaf_index, data[0], zero);
-    let witness = set.witness(leaf_index);
-    assert!(set.check_inclusion(witness, leaf_index, data[0]));
+    set.insert(leaf_index, data[0], zero).unwrap();
+    let witness = set.witness(leaf_index).unwrap();
+    assert!(set.check_inclusion(witness, leaf_index, data[0]).unwrap());
+    assert!(set.insert(leaf_index, data[1], Some(data[2])).is_err());
+    assert!(set.insert(8, data[1], zero).is_err());
+    assert!(set.witness(8).is_err());
+    let leaf = set.hasher.hash(vec![data[0]]).unwrap();
+    assert_eq!(set.leaf_index(leaf), Some(leaf_index));
+    assert_eq!(set.leaf_index(data[0]), None);
 }
 
 #[test]
 fn test_merkle_zeros() {
     use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr, FrRepr};
-    let hasher = Hasher::<Bn256>::new();
-    let mut set = MerkleTree::empty(hasher, 32);
-    set.insert(5, Fr::from_str("1").unwrap(), Some(Fr::zero()));
+    let hasher = Poseidon::<Bn256>::new();
+    let mut set = MerkleTree::empty(hasher, 32).unwrap();
+    set.insert(5, Fr::from_str("1").unwrap(), Some(Fr::zero())).unwrap();
     println!("{}", set.root());
-    set.insert(6, Fr::from_str("2").unwrap(), Some(Fr::zero()));
+    set.insert(6, Fr::from_str("2").unwrap(), Some(Fr::zero())).unwrap();
     println!("{}", set.root());
 }
+
+#[test]
+fn test_merkle_parallel() {
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    let hasher = Poseidon::<Bn256>::new();
+    let build = |offset: usize| {
+        let mut set = MerkleTree::empty(hasher.clone(), 16).unwrap();
+        for i in 0..8 {
+            let leaf = Fr::from_str(&format!("{}", offset + i)).unwrap();
+            set.update(offset + i, leaf).unwrap();
+        }
+        set.root()
+    };
+
+    // trees built from threads sharing the hasher match the sequential ones
+    let roots: Vec<Fr> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4).map(|k| s.spawn(move || build(k * 8))).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    for (k, root) in roots.iter().enumerate() {
+        assert_eq!(*root, build(k * 8));
+    }
+}
+
+#[test]
+fn test_merkle_hashers() {
+    use crate::poseidon2::Poseidon2;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    let leaf = Fr::from_str("7").unwrap();
+    let mut poseidon = MerkleTree::empty(Poseidon::<Bn256>::new(), 8).unwrap();
+    let mut poseidon2 = MerkleTree::empty(Poseidon2::<Bn256>::new(), 8).unwrap();
+    poseidon.insert(3, leaf, None).unwrap();
+    poseidon2.insert(3, leaf, None).unwrap();
+    assert_ne!(poseidon.root(), poseidon2.root());
+
+    let witness = poseidon2.witness(3).unwrap();
+    assert!(poseidon2.check_inclusion(witness.clone(), 3, leaf).unwrap());
+    assert!(!poseidon.check_inclusion(witness, 3, leaf).unwrap());
+}
+
+#[test]
+fn test_dense_merkle() {
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let hasher = Poseidon::<Bn256>::new();
+    let depth = 5;
+    let mut sparse = MerkleTree::empty(hasher.clone(), depth).unwrap();
+    let mut dense = DenseMerkleTree::empty(hasher, depth).unwrap();
+    assert_eq!(sparse.root(), dense.root());
+
+    for leaf_index in [0, 31, 6, 7, 6, 20].iter().cloned() {
+        let leaf = Fr::rand(&mut rng);
+        sparse.update(leaf_index, leaf).unwrap();
+        dense.update(leaf_index, leaf).unwrap();
+        assert_eq!(sparse.root(), dense.root());
+        assert_eq!(dense.leaf_index(leaf), Some(leaf_index));
+        for i in 0..1 << depth {
+            assert_eq!(sparse.witness(i).unwrap(), dense.witness(i).unwrap());
+        }
+    }
+
+    let data = Fr::rand(&mut rng);
+    dense.insert(9, data, Some(Fr::zero())).unwrap();
+    assert!(dense.insert(9, data, Some(Fr::zero())).is_err());
+    dense.insert(9, Fr::one(), Some(data)).unwrap();
+    let witness = dense.witness(9).unwrap();
+    assert!(dense
+        .check_inclusion(witness.clone(), 9, Fr::one())
+        .unwrap());
+    assert!(!dense.check_inclusion(witness, 9, data).unwrap());
+
+    // the lowest index of a repeated leaf, never an empty one
+    let repeated = Fr::rand(&mut rng);
+    for leaf_index in [17, 12, 25].iter().cloned() {
+        sparse.update(leaf_index, repeated).unwrap();
+        dense.update(leaf_index, repeated).unwrap();
+    }
+    sparse.update(12, Fr::zero()).unwrap();
+    dense.update(12, Fr::zero()).unwrap();
+    assert_eq!(sparse.leaf_index(repeated), Some(17));
+    assert_eq!(dense.leaf_index(repeated), Some(17));
+    assert_eq!(sparse.leaf_index(Fr::zero()), None);
+    assert_eq!(dense.leaf_index(Fr::zero()), None);
+
+    assert!(dense.update(1 << depth, data).is_err());
+    assert!(dense.witness(1 << depth).is_err());
+    assert!(DenseMerkleTree::empty(Poseidon::<Bn256>::new(), 64).is_err());
+}
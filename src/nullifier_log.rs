@@ -0,0 +1,339 @@
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::poseidon::Poseidon as PoseidonHasher;
+use crate::public::RLNProof;
+use crate::utils::{interpolate_line, read_fr};
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::Engine;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// Log of verified signals keyed by (epoch, nullifier)
+// used by verifiers to detect members signalling twice in an epoch.
+
+pub enum SignalStatus<E>
+where
+    E: Engine,
+{
+    // first signal of the member in the epoch
+    Fresh,
+    // same share as the recorded one, a replayed signal
+    Duplicate,
+    // another share under the same nullifier, the member is slashable
+    Spam { id_secret: E::Fr },
+}
+
+impl<E> PartialEq for SignalStatus<E>
+where
+    E: Engine,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SignalStatus::Fresh, SignalStatus::Fresh) => true,
+            (SignalStatus::Duplicate, SignalStatus::Duplicate) => true,
+            (SignalStatus::Spam { id_secret: a }, SignalStatus::Spam { id_secret: b }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<E> fmt::Debug for SignalStatus<E>
+where
+    E: Engine,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignalStatus::Fresh => write!(f, "Fresh"),
+            SignalStatus::Duplicate => write!(f, "Duplicate"),
+            SignalStatus::Spam { id_secret } => write!(f, "Spam {{ id_secret: {} }}", id_secret),
+        }
+    }
+}
+
+// first share seen under each nullifier of an epoch
+type Shares<F> = HashMap<F, (F, F)>;
+
+pub struct NullifierLog<E, H = PoseidonHasher<E>>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    // number of past epochs kept by prune
+    window: u64,
+    // hasher of the nullifiers, as used by the circuit of the proofs
+    hasher: H,
+    epochs: BTreeMap<<E::Fr as PrimeField>::Repr, Shares<E::Fr>>,
+}
+
+impl<E> NullifierLog<E>
+where
+    E: Engine,
+{
+    pub fn new(window: u64) -> NullifierLog<E> {
+        Self::new_with_hasher(window, PoseidonHasher::new())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<NullifierLog<E>, RlnError> {
+        Self::read_with_hasher(reader, PoseidonHasher::new())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<NullifierLog<E>, RlnError> {
+        Self::load_with_hasher(path, PoseidonHasher::new())
+    }
+}
+
+impl<E, H> NullifierLog<E, H>
+where
+    E: Engine,
+    H: Hasher<E>,
+{
+    pub fn new_with_hasher(window: u64, hasher: H) -> NullifierLog<E, H> {
+        NullifierLog {
+            window,
+            hasher,
+            epochs: BTreeMap::new(),
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    pub fn len(&self) -> usize {
+        self.epochs
+            .values()
+            .map(|nullifiers| nullifiers.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    // classifies the share against the first one seen under the nullifier,
+    // only the first share is kept
+    pub fn record(
+        &mut self,
+        epoch: E::Fr,
+        nullifier: E::Fr,
+        share: (E::Fr, E::Fr),
+    ) -> Result<SignalStatus<E>, RlnError> {
+        let nullifiers = self.epochs.entry(epoch.into_repr()).or_default();
+        let first = match nullifiers.get(&nullifier) {
+            Some(first) => *first,
+            None => {
+                nullifiers.insert(nullifier, share);
+                return Ok(SignalStatus::Fresh);
+            }
+        };
+        if first == share {
+            return Ok(SignalStatus::Duplicate);
+        }
+        let (a_0, a_1) = interpolate_line::<E>(first, share)?;
+        if self.hasher.hash(vec![a_1])? != nullifier {
+            return Err(RlnError::InvalidShares("shares do not open the nullifier"));
+        }
+        Ok(SignalStatus::Spam { id_secret: a_0 })
+    }
+
+    pub fn record_proof(&mut self, message: &RLNProof<E>) -> Result<SignalStatus<E>, RlnError> {
+        self.record(
+            message.epoch,
+            message.nullifier,
+            (message.share_x, message.share_y),
+        )
+    }
+
+    // drops every epoch older than current_epoch - window
+    pub fn prune(&mut self, current_epoch: E::Fr) {
+        let window = <E::Fr as PrimeField>::Repr::from(self.window);
+        let mut oldest = current_epoch.into_repr();
+        if oldest < window {
+            return;
+        }
+        oldest.sub_noborrow(&window);
+        self.epochs = self.epochs.split_off(&oldest);
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        writer.write_all(&self.window.to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        for (epoch, nullifiers) in self.epochs.iter() {
+            for (nullifier, (share_x, share_y)) in nullifiers.iter() {
+                epoch.write_le(&mut writer)?;
+                nullifier.into_repr().write_le(&mut writer)?;
+                share_x.into_repr().write_le(&mut writer)?;
+                share_y.into_repr().write_le(&mut writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_with_hasher<R: Read>(
+        mut reader: R,
+        hasher: H,
+    ) -> Result<NullifierLog<E, H>, RlnError> {
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        let mut log = NullifierLog::new_with_hasher(u64::from_le_bytes(buf), hasher);
+        reader.read_exact(&mut buf)?;
+        for _ in 0..u64::from_le_bytes(buf) {
+            let epoch = read_fr::<_, E>(&mut reader)?;
+            let nullifier = read_fr::<_, E>(&mut reader)?;
+            let share_x = read_fr::<_, E>(&mut reader)?;
+            let share_y = read_fr::<_, E>(&mut reader)?;
+            log.epochs
+                .entry(epoch.into_repr())
+                .or_default()
+                .insert(nullifier, (share_x, share_y));
+        }
+        Ok(log)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RlnError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_with_hasher<P: AsRef<Path>>(
+        path: P,
+        hasher: H,
+    ) -> Result<NullifierLog<E, H>, RlnError> {
+        Self::read_with_hasher(BufReader::new(File::open(path)?), hasher)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NullifierLog, SignalStatus};
+    use crate::hasher::Hasher;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use crate::poseidon2::Poseidon2;
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+
+    fn fr(n: u64) -> Fr {
+        Fr::from_str(&n.to_string()).unwrap()
+    }
+
+    // share and nullifier of the member with secret a_0 in epoch
+    fn signal(a_0: Fr, epoch: Fr, share_x: Fr) -> (Fr, (Fr, Fr)) {
+        signal_with_hasher(&PoseidonHasher::<Bn256>::new(), a_0, epoch, share_x)
+    }
+
+    fn signal_with_hasher<H: Hasher<Bn256>>(
+        hasher: &H,
+        a_0: Fr,
+        epoch: Fr,
+        share_x: Fr,
+    ) -> (Fr, (Fr, Fr)) {
+        let a_1 = hasher.hash(vec![a_0, epoch]).unwrap();
+        let mut share_y = a_1;
+        share_y.mul_assign(&share_x);
+        share_y.add_assign(&a_0);
+        (hasher.hash(vec![a_1]).unwrap(), (share_x, share_y))
+    }
+
+    #[test]
+    fn test_nullifier_log() {
+        let mut log = NullifierLog::<Bn256>::new(2);
+        let a_0 = fr(42);
+
+        let (nullifier, share_1) = signal(a_0, fr(10), fr(1));
+        let (_, share_2) = signal(a_0, fr(10), fr(2));
+        assert_eq!(
+            log.record(fr(10), nullifier, share_1).unwrap(),
+            SignalStatus::Fresh
+        );
+        assert_eq!(
+            log.record(fr(10), nullifier, share_1).unwrap(),
+            SignalStatus::Duplicate
+        );
+        assert_eq!(
+            log.record(fr(10), nullifier, share_2).unwrap(),
+            SignalStatus::Spam { id_secret: a_0 }
+        );
+
+        // next epoch gives a fresh nullifier
+        let (nullifier, share_3) = signal(a_0, fr(11), fr(1));
+        assert_eq!(
+            log.record(fr(11), nullifier, share_3).unwrap(),
+            SignalStatus::Fresh
+        );
+        assert_eq!(log.len(), 2);
+
+        // a forged share under a known nullifier
+        let forged = (fr(3), fr(4));
+        assert!(log.record(fr(11), nullifier, forged).is_err());
+
+        log.prune(fr(12));
+        assert_eq!(log.len(), 2);
+        log.prune(fr(13));
+        assert_eq!(log.len(), 1);
+        log.prune(fr(14));
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_nullifier_log_persistence() {
+        let mut log = NullifierLog::<Bn256>::new(5);
+        for epoch in 0..4 {
+            let (nullifier, share) = signal(fr(7), fr(epoch), fr(epoch + 100));
+            log.record(fr(epoch), nullifier, share).unwrap();
+        }
+
+        // unique per process so concurrent test runs do not share the file
+        let path = std::env::temp_dir().join(format!(
+            "rln_nullifier_log_test_persistence_{}",
+            std::process::id()
+        ));
+        log.save(&path).unwrap();
+        let restored = NullifierLog::<Bn256>::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut restored = restored.unwrap();
+
+        assert_eq!(restored.window(), 5);
+        assert_eq!(restored.len(), 4);
+        let (nullifier, share) = signal(fr(7), fr(2), fr(102));
+        assert_eq!(
+            restored.record(fr(2), nullifier, share).unwrap(),
+            SignalStatus::Duplicate
+        );
+    }
+
+    #[test]
+    fn test_nullifier_log_hasher() {
+        let hasher = Poseidon2::<Bn256>::new();
+        let mut log = NullifierLog::new_with_hasher(2, hasher.clone());
+        let a_0 = fr(42);
+
+        let (nullifier, share_1) = signal_with_hasher(&hasher, a_0, fr(10), fr(1));
+        let (_, share_2) = signal_with_hasher(&hasher, a_0, fr(10), fr(2));
+        assert_eq!(
+            log.record(fr(10), nullifier, share_1).unwrap(),
+            SignalStatus::Fresh
+        );
+        assert_eq!(
+            log.record(fr(10), nullifier, share_2).unwrap(),
+            SignalStatus::Spam { id_secret: a_0 }
+        );
+
+        // nullifiers of poseidon2 do not open under poseidon
+        let mut poseidon_log = NullifierLog::<Bn256>::new(2);
+        poseidon_log.record(fr(10), nullifier, share_1).unwrap();
+        assert!(poseidon_log.record(fr(10), nullifier, share_2).is_err());
+
+        let mut raw_log: Vec<u8> = Vec::new();
+        log.write(&mut raw_log).unwrap();
+        let mut restored = NullifierLog::read_with_hasher(raw_log.as_slice(), hasher).unwrap();
+        assert_eq!(
+            restored.record(fr(10), nullifier, share_2).unwrap(),
+            SignalStatus::Spam { id_secret: a_0 }
+        );
+    }
+}
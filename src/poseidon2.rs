@@ -0,0 +1,336 @@
+use crate::error::RlnError;
+use crate::hasher::Hasher;
+use crate::poseidon::cached;
+use crate::poseidon_utils::poseidon2::generate_poseidon2_constants;
+
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
+use sapling_crypto::bellman::pairing::Engine;
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+// Poseidon2 (https://eprint.iacr.org/2023/323) with the x^5 sbox.
+// Full rounds multiply by a cheap external matrix and partial rounds by
+// the internal matrix 1 + diag(d), the state goes through the external
+// matrix once before the first round. Hashing follows Poseidon, inputs
+// after a zero capacity element and the first element is the digest.
+
+// widest permutation, hashing up to 3 inputs
+pub const MAX_WIDTH: usize = 4;
+
+// full and partial rounds of a width t permutation at 128 bit security
+pub fn round_numbers(t: usize) -> Option<(usize, usize)> {
+    if !(2..=MAX_WIDTH).contains(&t) {
+        return None;
+    }
+    Some((8, 56))
+}
+
+#[derive(Clone)]
+pub struct Poseidon2Params<E: Engine> {
+    rf: usize,
+    rp: usize,
+    t: usize,
+    round_constants: Vec<E::Fr>,
+    internal_diagonal: Vec<E::Fr>,
+}
+
+impl<E: Engine> Poseidon2Params<E> {
+    pub fn new(t: usize) -> Result<Poseidon2Params<E>, RlnError> {
+        let (rf, rp) = round_numbers(t).ok_or(RlnError::InvalidWidth(t))?;
+        let (round_constants, internal_diagonal) = generate_poseidon2_constants::<E::Fr>(t, rf, rp);
+        Ok(Poseidon2Params {
+            rf,
+            rp,
+            t,
+            round_constants,
+            internal_diagonal,
+        })
+    }
+
+    // parameters of the engine at width t, generated once per process
+    pub fn cached(t: usize) -> Result<Arc<Poseidon2Params<E>>, RlnError> {
+        cached(t, Poseidon2Params::<E>::new)
+    }
+
+    pub fn width(&self) -> usize {
+        self.t
+    }
+
+    pub fn partial_round_len(&self) -> usize {
+        self.rp
+    }
+
+    pub fn full_round_half_len(&self) -> usize {
+        self.rf / 2
+    }
+
+    pub fn total_rounds(&self) -> usize {
+        self.rf + self.rp
+    }
+
+    // t per round, partial rounds only have the first one
+    pub fn round_constants(&self) -> &[E::Fr] {
+        &self.round_constants
+    }
+
+    pub fn internal_diagonal(&self) -> &[E::Fr] {
+        &self.internal_diagonal
+    }
+
+    // row major, 1 + I up to width 3 and M4 of the paper at width 4
+    pub fn external_matrix(&self) -> Vec<E::Fr> {
+        let entries: Vec<u64> = match self.t {
+            4 => vec![5, 7, 1, 3, 4, 6, 1, 1, 1, 3, 5, 7, 1, 1, 4, 6],
+            t => (0..t * t)
+                .map(|k| if k % (t + 1) == 0 { 2 } else { 1 })
+                .collect(),
+        };
+        entries
+            .into_iter()
+            .map(|v| E::Fr::from_str(&v.to_string()).expect("small"))
+            .collect()
+    }
+
+    pub fn permute(&self, state: &mut [E::Fr]) {
+        assert_eq!(state.len(), self.t);
+        let t = self.t;
+        let a1 = self.full_round_half_len();
+        let a2 = a1 + self.partial_round_len();
+
+        mul_external::<E>(state);
+        for round in 0..self.total_rounds() {
+            let c = &self.round_constants[round * t..(round + 1) * t];
+            if round < a1 || round >= a2 {
+                for (s, c) in state.iter_mut().zip(c.iter()) {
+                    s.add_assign(c);
+                    quintic::<E>(s);
+                }
+                mul_external::<E>(state);
+            } else {
+                state[0].add_assign(&c[0]);
+                quintic::<E>(&mut state[0]);
+                mul_internal::<E>(state, &self.internal_diagonal);
+            }
+        }
+    }
+}
+
+fn quintic<E: Engine>(s: &mut E::Fr) {
+    let mut b = *s;
+    b.square();
+    b.square();
+    s.mul_assign(&b);
+}
+
+fn sum<E: Engine>(state: &[E::Fr]) -> E::Fr {
+    let mut sum = E::Fr::zero();
+    for s in state.iter() {
+        sum.add_assign(s);
+    }
+    sum
+}
+
+fn mul_external<E: Engine>(state: &mut [E::Fr]) {
+    if state.len() == 4 {
+        // M4 in eight additions and four doublings
+        let mut t0 = state[0];
+        t0.add_assign(&state[1]);
+        let mut t1 = state[2];
+        t1.add_assign(&state[3]);
+        let mut t2 = state[1];
+        t2.double();
+        t2.add_assign(&t1);
+        let mut t3 = state[3];
+        t3.double();
+        t3.add_assign(&t0);
+        let mut t4 = t1;
+        t4.double();
+        t4.double();
+        t4.add_assign(&t3);
+        let mut t5 = t0;
+        t5.double();
+        t5.double();
+        t5.add_assign(&t2);
+        let mut t6 = t3;
+        t6.add_assign(&t5);
+        let mut t7 = t2;
+        t7.add_assign(&t4);
+        state.copy_from_slice(&[t6, t5, t7, t4]);
+    } else {
+        let sum = sum::<E>(state);
+        for s in state.iter_mut() {
+            s.add_assign(&sum);
+        }
+    }
+}
+
+fn mul_internal<E: Engine>(state: &mut [E::Fr], diagonal: &[E::Fr]) {
+    let sum = sum::<E>(state);
+    for (s, d) in state.iter_mut().zip(diagonal.iter()) {
+        s.mul_assign(d);
+        s.add_assign(&sum);
+    }
+}
+
+#[derive(Clone)]
+pub struct Poseidon2<E: Engine> {
+    _engine: PhantomData<E>,
+}
+
+impl<E: Engine> Default for Poseidon2<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Engine> Poseidon2<E> {
+    pub fn new() -> Poseidon2<E> {
+        Poseidon2 {
+            _engine: PhantomData,
+        }
+    }
+
+    pub fn hash(&self, inputs: Vec<E::Fr>) -> Result<E::Fr, RlnError> {
+        let num_inputs = inputs.len();
+        if !(1..MAX_WIDTH).contains(&num_inputs) {
+            return Err(RlnError::InvalidHashArity(num_inputs));
+        }
+        let t = num_inputs + 1;
+
+        let params = Poseidon2Params::<E>::cached(t)?;
+        let mut state = vec![E::Fr::zero()];
+        state.extend(inputs);
+        params.permute(&mut state);
+        Ok(state[0])
+    }
+}
+
+impl<E: Engine> Hasher<E> for Poseidon2<E> {
+    fn hash(&self, inputs: Vec<E::Fr>) -> Result<E::Fr, RlnError> {
+        Poseidon2::hash(self, inputs)
+    }
+}
+
+#[test]
+fn test_poseidon2_permutation() {
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::to_hex;
+
+    let permute = |t: usize| -> Vec<String> {
+        let mut state: Vec<Fr> = (0..t)
+            .map(|i| Fr::from_str(&i.to_string()).unwrap())
+            .collect();
+        Poseidon2Params::<Bn256>::new(t)
+            .unwrap()
+            .permute(&mut state);
+        state.iter().map(to_hex).collect()
+    };
+
+    // permutation of (0, 1, 2) from the reference poseidon2 bn256 instance
+    assert_eq!(
+        permute(3),
+        vec![
+            "0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+            "303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+            "1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+        ]
+    );
+    assert_eq!(
+        to_hex(&Poseidon2Params::<Bn256>::new(3).unwrap().round_constants()[0]),
+        "1d066a255517b7fd8bddd3a93f7804ef7f8fcde48bb4c37a59a09a1a97052816"
+    );
+
+    // permutations of (0, 1) and (0, 1, 2, 3) from the reference instances
+    // of widths 2 and 4 (HorizenLabs poseidon2, as in TACEO poseidon2)
+    assert_eq!(
+        permute(2),
+        vec![
+            "1d01e56f49579cec72319e145f06f6177f6c5253206e78c2689781452a31878b",
+            "0d189ec589c41b8cffa88cfc523618a055abe8192c70f75aa72fc514560f6c61",
+        ]
+    );
+    assert_eq!(
+        permute(4),
+        vec![
+            "01bd538c2ee014ed5141b29e9ae240bf8db3fe5b9a38629a9647cf8d76c01737",
+            "239b62e7db98aa3a2a8f6a0d2fa1709e7a35959aa6c7034814d9daa90cbac662",
+            "04cbb44c61d928ed06808456bf758cbf0c18d1e15a7b6dbc8245fa7515d5e3cb",
+            "2e11c5cff2a22c64d01304b778d78f6998eff1ab73163a35603f54794c30847a",
+        ]
+    );
+
+    // permutation of the zero state of width 4 from barretenberg
+    let mut state = vec![Fr::zero(); 4];
+    Poseidon2Params::<Bn256>::new(4)
+        .unwrap()
+        .permute(&mut state);
+    assert_eq!(
+        state.iter().map(to_hex).collect::<Vec<_>>(),
+        vec![
+            "18dfb8dc9b82229cff974efefc8df78b1ce96d9d844236b496785c698bc6732e",
+            "095c230d1d37a246e8d2d5a63b165fe0fade040d442f61e25f0590e5fb76f839",
+            "0bb9545846e1afa4fa3c97414a60a20fc4949f537a68cceca34c5ce71e28aa59",
+            "18a4f34c9c6f99335ff7638b82aeed9018026618358873c982bbdde265b2ed6d",
+        ]
+    );
+
+    assert!(Poseidon2Params::<Bn256>::new(1).is_err());
+    assert!(Poseidon2Params::<Bn256>::new(MAX_WIDTH + 1).is_err());
+}
+
+#[test]
+fn test_poseidon2_hash() {
+    use crate::poseidon::Poseidon;
+    use sapling_crypto::bellman::pairing::bls12_381::{self, Bls12};
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+
+    let hasher = Poseidon2::<Bn256>::new();
+    let inputs: Vec<Fr> = ["1", "2", "3"]
+        .iter()
+        .map(|e| Fr::from_str(e).unwrap())
+        .collect();
+
+    // the external matrix as a dense product
+    let params = Poseidon2Params::<Bn256>::cached(4).unwrap();
+    let matrix = params.external_matrix();
+    let mut state = inputs.clone();
+    state.push(Fr::from_str("4").unwrap());
+    let mut expected = vec![Fr::zero(); 4];
+    for (i, e) in expected.iter_mut().enumerate() {
+        for (j, s) in state.iter().enumerate() {
+            let mut term = *s;
+            term.mul_assign(&matrix[i * 4 + j]);
+            e.add_assign(&term);
+        }
+    }
+    mul_external::<Bn256>(&mut state);
+    assert_eq!(state, expected);
+
+    for n in 1..MAX_WIDTH {
+        let digest = hasher.hash(inputs[..n].to_vec()).unwrap();
+        let mut state = vec![Fr::zero()];
+        state.extend_from_slice(&inputs[..n]);
+        Poseidon2Params::<Bn256>::cached(n + 1)
+            .unwrap()
+            .permute(&mut state);
+        assert_eq!(digest, state[0]);
+        assert_ne!(
+            digest,
+            Poseidon::<Bn256>::new().hash(inputs[..n].to_vec()).unwrap()
+        );
+    }
+    assert!(hasher.hash(vec![]).is_err());
+    assert!(hasher.hash(vec![Fr::zero(); MAX_WIDTH]).is_err());
+
+    // any engine gets its own constants
+    let bls = Poseidon2::<Bls12>::new()
+        .hash(vec![bls12_381::Fr::one(); 3])
+        .unwrap();
+    assert_ne!(
+        bls,
+        Poseidon::<Bls12>::new()
+            .hash(vec![bls12_381::Fr::one(); 3])
+            .unwrap()
+    );
+}
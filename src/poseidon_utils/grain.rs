@@ -0,0 +1,288 @@
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+// Parameter generation of the Poseidon reference implementation
+// (generate_parameters_grain.sage) for prime fields and the x^5 sbox.
+// Round constants are sampled from the Grain LFSR with rejection,
+// then the LFSR keeps going to sample the Cauchy MDS matrix
+// 1 / (x_i + y_j) over 2t distinct elements, resampled until it passes
+// the invariant subspace checks of the script.
+
+pub struct Grain {
+    state: Vec<bool>,
+}
+
+impl Grain {
+    // seeded with field type (prime), sbox (x^alpha),
+    // field size, width, full and partial rounds
+    pub fn new(field_bits: usize, t: usize, rf: usize, rp: usize) -> Grain {
+        let mut state = Vec::with_capacity(80);
+        let mut append = |value: usize, width: usize| {
+            for i in (0..width).rev() {
+                state.push((value >> i) & 1 == 1);
+            }
+        };
+        append(1, 2);
+        append(0, 4);
+        append(field_bits, 12);
+        append(t, 12);
+        append(rf, 10);
+        append(rp, 10);
+        append((1 << 30) - 1, 30);
+
+        let mut grain = Grain { state };
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.remove(0);
+        self.state.push(bit);
+        bit
+    }
+
+    // output bits are kept only when the preceding bit is set
+    fn next_bit(&mut self) -> bool {
+        loop {
+            let keep = self.step();
+            let bit = self.step();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    // big endian integer of the next bits
+    fn next_repr<F: PrimeField>(&mut self) -> F::Repr {
+        let mut repr = F::Repr::default();
+        for _ in 0..F::NUM_BITS {
+            repr.shl(1);
+            if self.next_bit() {
+                repr.as_mut()[0] |= 1;
+            }
+        }
+        repr
+    }
+
+    // next field element, resampling integers above the modulus
+    pub fn field_element<F: PrimeField>(&mut self) -> F {
+        loop {
+            if let Ok(e) = F::from_repr(self.next_repr::<F>()) {
+                return e;
+            }
+        }
+    }
+
+    // next integer reduced modulo the field, for the MDS matrix
+    pub fn reduced_field_element<F: PrimeField>(&mut self) -> F {
+        let mut repr = self.next_repr::<F>();
+        let modulus = F::char();
+        // 2^NUM_BITS is below twice the modulus
+        if repr >= modulus {
+            repr.sub_noborrow(&modulus);
+        }
+        F::from_repr(repr).expect("reduced below the modulus")
+    }
+}
+
+// round constants and row major MDS matrix of a width t permutation
+pub fn generate_constants<F: PrimeField>(t: usize, rf: usize, rp: usize) -> (Vec<F>, Vec<F>) {
+    let mut grain = Grain::new(F::NUM_BITS as usize, t, rf, rp);
+
+    let round_constants = (0..(rf + rp) * t)
+        .map(|_| grain.field_element::<F>())
+        .collect();
+
+    loop {
+        let samples: Vec<F> = (0..2 * t)
+            .map(|_| grain.reduced_field_element::<F>())
+            .collect();
+        let distinct = samples
+            .iter()
+            .enumerate()
+            .all(|(i, a)| samples[i + 1..].iter().all(|b| a != b));
+        if !distinct {
+            continue;
+        }
+
+        let (xs, ys) = samples.split_at(t);
+        let mut mds_matrix = Vec::with_capacity(t * t);
+        for x in xs.iter() {
+            for y in ys.iter() {
+                let mut sum = *x;
+                sum.add_assign(y);
+                match sum.inverse() {
+                    Some(entry) => mds_matrix.push(entry),
+                    None => break,
+                }
+            }
+        }
+        if mds_matrix.len() == t * t && is_secure_mds(&mds_matrix, t) {
+            return (round_constants, mds_matrix);
+        }
+    }
+}
+
+// row major t x t matrix product
+fn mat_mul<F: PrimeField>(a: &[F], b: &[F], t: usize) -> Vec<F> {
+    let mut product = vec![F::zero(); t * t];
+    for i in 0..t {
+        for k in 0..t {
+            for j in 0..t {
+                let mut term = a[i * t + k];
+                term.mul_assign(&b[k * t + j]);
+                product[i * t + j].add_assign(&term);
+            }
+        }
+    }
+    product
+}
+
+// row vector times matrix
+fn vec_mat_mul<F: PrimeField>(v: &[F], m: &[F], t: usize) -> Vec<F> {
+    (0..t)
+        .map(|j| {
+            let mut acc = F::zero();
+            for (k, x) in v.iter().enumerate() {
+                let mut term = *x;
+                term.mul_assign(&m[k * t + j]);
+                acc.add_assign(&term);
+            }
+            acc
+        })
+        .collect()
+}
+
+// matrix times column vector
+fn mat_vec_mul<F: PrimeField>(m: &[F], v: &[F], t: usize) -> Vec<F> {
+    (0..t)
+        .map(|i| {
+            let mut acc = F::zero();
+            for (k, x) in v.iter().enumerate() {
+                let mut term = m[i * t + k];
+                term.mul_assign(x);
+                acc.add_assign(&term);
+            }
+            acc
+        })
+        .collect()
+}
+
+// rank of the vectors by gaussian elimination
+fn rank<F: PrimeField>(mut vectors: Vec<Vec<F>>) -> usize {
+    let mut rank = 0;
+    let columns = vectors.first().map_or(0, |v| v.len());
+    for column in 0..columns {
+        let pivot = match (rank..vectors.len()).find(|&i| !vectors[i][column].is_zero()) {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        vectors.swap(rank, pivot);
+        let (pivot_rows, rest) = vectors.split_at_mut(rank + 1);
+        let pivot_row = &pivot_rows[rank];
+        let inverse = pivot_row[column].inverse().expect("pivot is not zero");
+        for row in rest.iter_mut() {
+            let mut factor = row[column];
+            factor.mul_assign(&inverse);
+            for (e, p) in row[column..].iter_mut().zip(pivot_row[column..].iter()) {
+                let mut term = *p;
+                term.mul_assign(&factor);
+                e.sub_assign(&term);
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+// e_0 and its images under the powers 1..n of the row vector or column vector map
+fn orbit<F: PrimeField>(t: usize, n: usize, map: impl Fn(&[F]) -> Vec<F>) -> Vec<Vec<F>> {
+    let mut e_0 = vec![F::zero(); t];
+    e_0[0] = F::one();
+    let mut orbit = vec![e_0];
+    for k in 0..n {
+        let next = map(&orbit[k]);
+        orbit.push(next);
+    }
+    orbit
+}
+
+// Algorithms 1 to 3 of generate_parameters_grain.sage with one sbox per
+// partial round: no power of the matrix keeps a subspace on which the
+// sbox input stays zero, so no subspace trail skips every partial round.
+fn is_secure_mds<F: PrimeField>(mds_matrix: &[F], t: usize) -> bool {
+    // algorithm 1, for M^i with i up to t - 1
+    let mut power = mds_matrix.to_vec();
+    for i in 1..t {
+        if i > 1 {
+            power = mat_mul(&power, mds_matrix, t);
+        }
+        let diagonal = power[0];
+        let scalar = (0..t * t).all(|k| {
+            if k % (t + 1) == 0 {
+                power[k] == diagonal
+            } else {
+                power[k].is_zero()
+            }
+        });
+        if scalar {
+            return false;
+        }
+
+        // rows k of the orbit are the first rows of M^(i k)
+        let rows = orbit(t, t - 1, |row| vec_mat_mul(row, &power, t));
+        // M^(i j) for j up to i must not map the subspace x_0 = 0 onto itself
+        if rows[1..=i]
+            .iter()
+            .any(|row| row[1..].iter().all(|e| e.is_zero()))
+        {
+            return false;
+        }
+        // the vectors with x_0 = 0 under every M^(i k) form the largest invariant
+        // subspace inside x_0 = 0, it contains every eigenvector of the script test
+        if rank(rows) < t {
+            return false;
+        }
+    }
+
+    // algorithms 2 and 3, e_0 generates the whole space under M^r for r up to 4t
+    let mut power = mds_matrix.to_vec();
+    for r in 1..=4 * t {
+        if r > 1 {
+            power = mat_mul(&power, mds_matrix, t);
+        }
+        if rank(orbit(t, t - 1, |v| mat_vec_mul(&power, v, t))) < t {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn test_mds_subspace_checks() {
+    use sapling_crypto::bellman::pairing::bn256::Fr;
+    let fr = |v: u64| Fr::from_str(&v.to_string()).unwrap();
+
+    // the circomlib matrices pass
+    for t in 2..=17 {
+        let (rf, rp) = crate::poseidon::round_numbers(t).unwrap();
+        let (_, mds_matrix) = generate_constants::<Fr>(t, rf, rp);
+        assert!(is_secure_mds(&mds_matrix, t));
+    }
+
+    // M^2 is the identity
+    let rotation = vec![fr(0), fr(1), fr(1), fr(0)];
+    assert!(!is_secure_mds(&rotation, 2));
+    // e_0 spans an invariant subspace
+    let upper = vec![fr(2), fr(1), fr(0), fr(3)];
+    assert!(!is_secure_mds(&upper, 2));
+    // e_1 is an eigenvector with x_0 = 0
+    let lower = vec![fr(2), fr(0), fr(1), fr(3)];
+    assert!(!is_secure_mds(&lower, 2));
+    // invertible and without invariant subspace in x_0 = 0
+    let mixing = vec![fr(2), fr(1), fr(1), fr(1)];
+    assert!(is_secure_mds(&mixing, 2));
+}
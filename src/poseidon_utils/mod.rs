@@ -1,8 +1,14 @@
 use sapling_crypto::bellman::pairing::ff::{PrimeField, from_hex};
 use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
 
+pub mod bls12_381_x5_3;
+pub mod bls12_381_x5_2;
+pub mod bn254_x5_4;
 pub mod bn254_x5_3;
 pub mod bn254_x5_2;
+pub mod grain;
+pub mod poseidon2;
+pub mod sparse;
 
 
 pub fn decode_hex(s: &str) -> Vec<u8> {
@@ -53,6 +59,36 @@ pub fn parse_matrix<F: PrimeField>(mds_entries: Vec<Vec<&str>>) -> Vec<F> {
 // 	parse_vec(bn254_x5_3_result::RESULT.to_vec())
 // }
 
+pub fn get_rounds_poseidon_bls12_381_x5_3<F: PrimeField>() -> Vec<F> {
+	parse_vec(bls12_381_x5_3::ROUND_CONSTS.to_vec())
+}
+
+pub fn get_rounds_poseidon_bls12_381_x5_2<F: PrimeField>() -> Vec<F> {
+	parse_vec(bls12_381_x5_2::ROUND_CONSTS.to_vec())
+}
+
+pub fn get_mds_poseidon_bls12_381_x5_3<F: PrimeField>() -> Vec<F> {
+	parse_matrix(
+		bls12_381_x5_3::MDS_ENTRIES
+			.iter()
+			.map(|x| x.to_vec())
+			.collect::<Vec<_>>(),
+	)
+}
+
+pub fn get_mds_poseidon_bls12_381_x5_2<F: PrimeField>() -> Vec<F> {
+	parse_matrix(
+		bls12_381_x5_2::MDS_ENTRIES
+			.iter()
+			.map(|x| x.to_vec())
+			.collect::<Vec<_>>(),
+	)
+}
+
+pub fn get_rounds_poseidon_bn254_x5_4<F: PrimeField>() -> Vec<F> {
+	parse_vec(bn254_x5_4::ROUND_CONSTS.to_vec())
+}
+
 pub fn get_rounds_poseidon_bn254_x5_3<F: PrimeField>() -> Vec<F> {
 	parse_vec(bn254_x5_3::ROUND_CONSTS.to_vec())
 }
@@ -61,6 +97,15 @@ pub fn get_rounds_poseidon_bn254_x5_2<F: PrimeField>() -> Vec<F> {
 	parse_vec(bn254_x5_2::ROUND_CONSTS.to_vec())
 }
 
+pub fn get_mds_poseidon_bn254_x5_4<F: PrimeField>() -> Vec<F> {
+	parse_matrix(
+		bn254_x5_4::MDS_ENTRIES
+			.iter()
+			.map(|x| x.to_vec())
+			.collect::<Vec<_>>(),
+	)
+}
+
 pub fn get_mds_poseidon_bn254_x5_3<F: PrimeField>() -> Vec<F> {
 	parse_matrix(
 		bn254_x5_3::MDS_ENTRIES
@@ -0,0 +1,212 @@
+use sapling_crypto::bellman::pairing::bn256::Fr;
+use sapling_crypto::bellman::pairing::ff::{from_hex, Field, PrimeField, PrimeFieldRepr};
+
+use crate::poseidon_utils::grain::Grain;
+
+// Parameter generation of the Poseidon2 reference implementation
+// (poseidon2_rust_params.sage). The Grain LFSR is seeded as for Poseidon
+// but samples a single round constant per partial round. The internal
+// matrix is 1 + diag(d), with d fixed for widths 2 and 3. For wider
+// states the reference script samples d outside the LFSR, so bn254 takes
+// the diagonal of the published instance (HorizenLabs poseidon2, also
+// used by barretenberg) and other fields sample d from the LFSR after the
+// round constants until the minimal polynomials of the first 2t powers of
+// the matrix are irreducible of degree t, the condition checked by the
+// reference script.
+
+// internal diagonal of the reference width 4 instance
+const DIAGONAL_4: [&str; 4] = [
+    "0x10dc6e9c006ea38b04b1e03b4bd9490c0d03f98929ca1d7fb56821fd19d3b6e7",
+    "0x0c28145b6a44df3e0149b3d0a30b3bb599df9756d4dd9b84a86b38cfb45a740b",
+    "0x00544b8338791518b2c7645a50392798b21f75bb60e3596170067d00141cac15",
+    "0x222c01175718386f2e2e82eb122789e352e105a3b8fa852613bc534433ee428b",
+];
+
+// round constants, zero padded in partial rounds, and internal diagonal
+pub fn generate_poseidon2_constants<F: PrimeField>(
+    t: usize,
+    rf: usize,
+    rp: usize,
+) -> (Vec<F>, Vec<F>) {
+    let mut grain = Grain::new(F::NUM_BITS as usize, t, rf, rp);
+
+    let mut round_constants = Vec::with_capacity((rf + rp) * t);
+    for round in 0..rf + rp {
+        if round >= rf / 2 && round < rf / 2 + rp {
+            round_constants.push(grain.field_element::<F>());
+            round_constants.resize(round_constants.len() + t - 1, F::zero());
+        } else {
+            for _ in 0..t {
+                round_constants.push(grain.field_element::<F>());
+            }
+        }
+    }
+
+    let small = |d: &[u64]| -> Vec<F> {
+        d.iter()
+            .map(|&v| F::from_repr(F::Repr::from(v)).expect("small"))
+            .collect()
+    };
+    let diagonal = match t {
+        2 => small(&[1, 2]),
+        3 => small(&[1, 1, 2]),
+        4 if F::char().as_ref() == Fr::char().as_ref() => DIAGONAL_4
+            .iter()
+            .map(|d| from_hex(d).expect("reference diagonal"))
+            .collect(),
+        _ => loop {
+            let diagonal: Vec<F> = (0..t).map(|_| grain.field_element::<F>()).collect();
+            if irreducible_powers(&diagonal) {
+                break diagonal;
+            }
+        },
+    };
+    (round_constants, diagonal)
+}
+
+fn irreducible_powers<F: PrimeField>(diagonal: &[F]) -> bool {
+    let t = diagonal.len();
+    let mut matrix = vec![F::one(); t * t];
+    for i in 0..t {
+        matrix[i * t + i].add_assign(&diagonal[i]);
+    }
+    let mut power = matrix.clone();
+    for _ in 0..2 * t {
+        // an irreducible characteristic polynomial is the minimal one
+        if !irreducible(&charpoly(&power, t)) {
+            return false;
+        }
+        power = mul_matrix(&matrix, &power, t);
+    }
+    true
+}
+
+fn mul_matrix<F: Field>(a: &[F], b: &[F], n: usize) -> Vec<F> {
+    let mut product = vec![F::zero(); n * n];
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let mut term = a[i * n + k];
+                term.mul_assign(&b[k * n + j]);
+                product[i * n + j].add_assign(&term);
+            }
+        }
+    }
+    product
+}
+
+// monic characteristic polynomial, lowest coefficient first,
+// from the Faddeev-LeVerrier recurrence
+fn charpoly<F: PrimeField>(matrix: &[F], n: usize) -> Vec<F> {
+    let mut coeffs = vec![F::zero(); n + 1];
+    coeffs[n] = F::one();
+    let mut m = vec![F::zero(); n * n];
+    for k in 1..=n {
+        for i in 0..n {
+            m[i * n + i].add_assign(&coeffs[n + 1 - k]);
+        }
+        let am = mul_matrix(matrix, &m, n);
+        let mut trace = F::zero();
+        for i in 0..n {
+            trace.add_assign(&am[i * n + i]);
+        }
+        let k_inverse = F::from_repr(F::Repr::from(k as u64))
+            .expect("small")
+            .inverse()
+            .expect("nonzero");
+        trace.mul_assign(&k_inverse);
+        trace.negate();
+        coeffs[n - k] = trace;
+        m = am;
+    }
+    coeffs
+}
+
+// Rabin's test, x^(p^n) = x mod f and x^(p^(n/q)) - x coprime
+// to f for every prime q dividing the degree n,
+// divisibility by % as is_multiple_of needs rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn irreducible<F: PrimeField>(f: &[F]) -> bool {
+    let n = f.len() - 1;
+    let mut x = vec![F::zero(); n];
+    x[1] = F::one();
+
+    let frobenius = |g: &[F], times: usize| -> Vec<F> {
+        let mut g = g.to_vec();
+        for _ in 0..times {
+            g = pow_char_mod(&g, f);
+        }
+        g
+    };
+
+    for q in (2..=n).filter(|q| n % q == 0 && (2..*q).all(|d| q % d != 0)) {
+        let mut h = frobenius(&x, n / q);
+        h[1].sub_assign(&F::one());
+        if degree(&gcd(f.to_vec(), h)) != Some(0) {
+            return false;
+        }
+    }
+    frobenius(&x, n) == x
+}
+
+fn degree<F: Field>(g: &[F]) -> Option<usize> {
+    g.iter().rposition(|c| !c.is_zero())
+}
+
+// remainder of a by b
+fn rem<F: Field>(mut a: Vec<F>, b: &[F]) -> Vec<F> {
+    let db = degree(b).expect("nonzero divisor");
+    let lead = b[db].inverse().expect("nonzero");
+    while let Some(da) = degree(&a) {
+        if da < db {
+            break;
+        }
+        let mut factor = a[da];
+        factor.mul_assign(&lead);
+        for (i, c) in b[..=db].iter().enumerate() {
+            let mut term = *c;
+            term.mul_assign(&factor);
+            a[da - db + i].sub_assign(&term);
+        }
+    }
+    a
+}
+
+fn gcd<F: Field>(mut a: Vec<F>, mut b: Vec<F>) -> Vec<F> {
+    while degree(&b).is_some() {
+        let r = rem(a, &b);
+        a = b;
+        b = r;
+    }
+    a
+}
+
+fn mul_mod<F: Field>(a: &[F], b: &[F], f: &[F]) -> Vec<F> {
+    let mut product = vec![F::zero(); a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            let mut term = *x;
+            term.mul_assign(y);
+            product[i + j].add_assign(&term);
+        }
+    }
+    let mut r = rem(product, f);
+    r.truncate(f.len() - 1);
+    r
+}
+
+// g^p mod f for the characteristic p
+fn pow_char_mod<F: PrimeField>(g: &[F], f: &[F]) -> Vec<F> {
+    let mut acc = vec![F::zero(); f.len() - 1];
+    acc[0] = F::one();
+    let modulus = F::char();
+    for limb in modulus.as_ref().iter().rev() {
+        for i in (0..64).rev() {
+            acc = mul_mod(&acc, &acc, f);
+            if (limb >> i) & 1 == 1 {
+                acc = mul_mod(&acc, g, f);
+            }
+        }
+    }
+    acc
+}
@@ -0,0 +1,157 @@
+use sapling_crypto::bellman::pairing::ff::Field;
+
+// Equivalent form of the permutation with cheap partial rounds,
+// following appendix B of the Poseidon paper.
+// Round constants of the partial rounds are carried forward through the
+// mds matrix, so that a partial round only adds one to the first element.
+// The mds matrix of each partial round is split as M = S * D, where S is
+// sparse and D leaves the first element alone. D commutes with the sbox of
+// the first element, so it is folded into the matrix of the previous round,
+// down to the last full round before the partial ones.
+
+#[derive(Clone)]
+pub struct SparseConstants<F: Field> {
+    // one round constant per partial round, on the first element
+    pub round_constants: Vec<F>,
+    // mds matrix of the last full round before the partial rounds
+    pub pre_sparse_matrix: Vec<F>,
+    // first row then first column under the diagonal, per partial round
+    pub sparse_matrices: Vec<F>,
+}
+
+impl<F: Field> SparseConstants<F> {
+    pub fn empty() -> SparseConstants<F> {
+        SparseConstants {
+            round_constants: Vec::new(),
+            pre_sparse_matrix: Vec::new(),
+            sparse_matrices: Vec::new(),
+        }
+    }
+}
+
+pub fn sparse_constants<F: Field>(
+    t: usize,
+    rf: usize,
+    rp: usize,
+    round_constants: &[F],
+    mds_matrix: &[F],
+) -> SparseConstants<F> {
+    let half = rf / 2;
+
+    let mut constants = round_constants.to_vec();
+    let mut carry = vec![F::zero(); t];
+    for round in half..half + rp {
+        let c = &mut constants[round * t..(round + 1) * t];
+        for (ci, carried) in c.iter_mut().zip(carry.iter()) {
+            ci.add_assign(carried);
+        }
+        let mut rest = c.to_vec();
+        rest[0] = F::zero();
+        for ci in c[1..].iter_mut() {
+            *ci = F::zero();
+        }
+        carry = mul_vector(mds_matrix, &rest);
+    }
+    // the first full round after the partial ones takes the rest
+    let round = half + rp;
+    for (ci, carried) in constants[round * t..(round + 1) * t].iter_mut().zip(carry.iter()) {
+        ci.add_assign(carried);
+    }
+
+    // factored from the last partial round backwards
+    let mut matrix = mds_matrix.to_vec();
+    let mut sparse_matrices = Vec::with_capacity(rp);
+    for _ in 0..rp {
+        let inner: Vec<F> = (1..t)
+            .flat_map(|i| matrix[i * t + 1..(i + 1) * t].to_vec())
+            .collect();
+
+        // w = row * inner^-1, that is inner^T * w = row
+        let transposed: Vec<F> = (0..(t - 1) * (t - 1))
+            .map(|k| inner[(k % (t - 1)) * (t - 1) + k / (t - 1)])
+            .collect();
+        let w = solve(transposed, matrix[1..t].to_vec()).expect("invertible mds submatrix");
+
+        let mut sparse = vec![matrix[0]];
+        sparse.extend(w);
+        sparse.extend((1..t).map(|i| matrix[i * t]));
+        sparse_matrices.push(sparse);
+
+        let mut d = vec![F::zero(); t * t];
+        d[0] = F::one();
+        for i in 1..t {
+            d[i * t + 1..(i + 1) * t].copy_from_slice(&inner[(i - 1) * (t - 1)..i * (t - 1)]);
+        }
+        matrix = mul_matrix(&d, mds_matrix, t);
+    }
+    sparse_matrices.reverse();
+
+    SparseConstants {
+        round_constants: constants,
+        pre_sparse_matrix: matrix,
+        sparse_matrices: sparse_matrices.concat(),
+    }
+}
+
+fn mul_vector<F: Field>(matrix: &[F], v: &[F]) -> Vec<F> {
+    matrix
+        .chunks(v.len())
+        .map(|row| {
+            let mut acc = F::zero();
+            for (m, x) in row.iter().zip(v.iter()) {
+                let mut term = *m;
+                term.mul_assign(x);
+                acc.add_assign(&term);
+            }
+            acc
+        })
+        .collect()
+}
+
+fn mul_matrix<F: Field>(a: &[F], b: &[F], n: usize) -> Vec<F> {
+    let mut product = vec![F::zero(); n * n];
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let mut term = a[i * n + k];
+                term.mul_assign(&b[k * n + j]);
+                product[i * n + j].add_assign(&term);
+            }
+        }
+    }
+    product
+}
+
+// gaussian elimination of a row major square system, None if singular
+fn solve<F: Field>(mut matrix: Vec<F>, mut rhs: Vec<F>) -> Option<Vec<F>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n).find(|&row| !matrix[row * n + col].is_zero())?;
+        for k in 0..n {
+            matrix.swap(pivot * n + k, col * n + k);
+        }
+        rhs.swap(pivot, col);
+
+        let inverse = matrix[col * n + col].inverse()?;
+        for k in 0..n {
+            matrix[col * n + k].mul_assign(&inverse);
+        }
+        rhs[col].mul_assign(&inverse);
+
+        for row in 0..n {
+            let factor = matrix[row * n + col];
+            if row == col || factor.is_zero() {
+                continue;
+            }
+            for k in 0..n {
+                let mut term = matrix[col * n + k];
+                term.mul_assign(&factor);
+                matrix[row * n + k].sub_assign(&term);
+            }
+            let mut term = rhs[col];
+            term.mul_assign(&factor);
+            rhs[row].sub_assign(&term);
+        }
+    }
+    Some(rhs)
+}
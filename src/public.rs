@@ -1,123 +1,972 @@
-use crate::circuit::poseidon::PoseidonCircuit;
-use crate::circuit::rln::{RLNCircuit, RLNInputs};
-use crate::merkle::MerkleTree;
-use crate::poseidon::{Poseidon as PoseidonHasher, PoseidonParams};
-use crate::utils::{read_inputs, read_uncompressed_proof, write_uncompressed_proof};
+use crate::batch;
+#[cfg(feature = "prover")]
+use crate::circuit::{
+    hasher::HasherGadget,
+    poseidon::PoseidonCircuit,
+    rln::{RLNCircuit, RLNInputs},
+};
+use crate::epoch::EpochConfig;
+use crate::error::RlnError;
+#[cfg(feature = "prover")]
+use crate::hasher::Hasher;
+#[cfg(feature = "prover")]
+use crate::merkle::{witness_root, MembershipTree};
+#[cfg(feature = "prover")]
+use crate::poseidon::PoseidonParams;
+use crate::utils::{
+    hash_signal, interpolate_line, read_fr, read_inputs, read_uncompressed_proof, secure_rng,
+    seeded_rng, write_uncompressed_proof,
+};
 use bellman::groth16::generate_random_parameters;
 use bellman::groth16::{create_proof, prepare_verifying_key, verify_proof};
 use bellman::groth16::{create_random_proof, Parameters, Proof};
+use bellman::groth16::{PreparedVerifyingKey, VerifyingKey};
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use bellman::pairing::{CurveAffine, EncodedPoint, Engine};
 use bellman::{Circuit, ConstraintSystem, SynthesisError};
-use rand::{Rand, SeedableRng, XorShiftRng};
+use rand::{Rand, Rng, SeedableRng, XorShiftRng};
 use std::io::{self, Error, ErrorKind, Read, Write};
 
-pub struct RLN<E>
+// A signal proof together with the public inputs it was made for.
+pub struct RLNProof<E>
 where
     E: Engine,
+{
+    pub proof: Proof<E>,
+    pub root: E::Fr,
+    pub epoch: E::Fr,
+    pub share_x: E::Fr,
+    pub share_y: E::Fr,
+    pub nullifier: E::Fr,
+    pub rln_identifier: E::Fr,
+}
+
+impl<E> RLNProof<E>
+where
+    E: Engine,
+{
+    pub fn public_inputs(&self) -> Vec<E::Fr> {
+        vec![
+            self.root,
+            self.epoch,
+            self.share_x,
+            self.share_y,
+            self.nullifier,
+            self.rln_identifier,
+        ]
+    }
+
+    // uncompressed proof followed by the public inputs
+    // in the order of RLNInputs::write_public_inputs
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), RlnError> {
+        write_uncompressed_proof(self.proof.clone(), &mut writer)?;
+        for input in self.public_inputs().iter() {
+            input.into_repr().write_le(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<RLNProof<E>, RlnError> {
+        let proof = read_uncompressed_proof(&mut reader)?;
+        Ok(RLNProof {
+            proof,
+            root: read_fr::<_, E>(&mut reader)?,
+            epoch: read_fr::<_, E>(&mut reader)?,
+            share_x: read_fr::<_, E>(&mut reader)?,
+            share_y: read_fr::<_, E>(&mut reader)?,
+            nullifier: read_fr::<_, E>(&mut reader)?,
+            rln_identifier: read_fr::<_, E>(&mut reader)?,
+        })
+    }
+}
+
+// Verification side of RLN, needs only the verifying key
+// so verifier nodes do not have to load the proving parameters.
+// Proofs made for another application than rln_identifier are rejected.
+pub struct RLNVerifier<E>
+where
+    E: Engine,
+{
+    verifying_key: VerifyingKey<E>,
+    prepared_verifying_key: PreparedVerifyingKey<E>,
+    rln_identifier: E::Fr,
+}
+
+impl<E> RLNVerifier<E>
+where
+    E: Engine,
+{
+    pub fn new(
+        verifying_key: VerifyingKey<E>,
+        rln_identifier: E::Fr,
+    ) -> Result<RLNVerifier<E>, RlnError> {
+        // root, epoch, share_x, share_y, nullifier, rln_identifier
+        if verifying_key.ic.len() != 7 {
+            return Err(SynthesisError::MalformedVerifyingKey.into());
+        }
+        let prepared_verifying_key = prepare_verifying_key(&verifying_key);
+        Ok(RLNVerifier {
+            verifying_key,
+            prepared_verifying_key,
+            rln_identifier,
+        })
+    }
+
+    /// Reads the key written by `RLN::export_verifier_key`.
+    pub fn new_with_raw_verifier_key<R: Read>(
+        raw_verifier_key: R,
+        rln_identifier: E::Fr,
+    ) -> Result<RLNVerifier<E>, RlnError> {
+        let verifying_key = VerifyingKey::<E>::read(raw_verifier_key)?;
+        Self::new(verifying_key, rln_identifier)
+    }
+
+    pub fn rln_identifier(&self) -> E::Fr {
+        self.rln_identifier
+    }
+
+    pub fn verify<R: Read>(
+        &self,
+        uncompresed_proof: R,
+        raw_public_inputs: R,
+    ) -> Result<bool, RlnError> {
+        let proof = read_uncompressed_proof(uncompresed_proof)?;
+        // in the order of RLNProof::public_inputs
+        let public_inputs = read_inputs::<_, E>(raw_public_inputs, 6)?;
+        if public_inputs[5] != self.rln_identifier {
+            return Ok(false);
+        }
+        let success = verify_proof(&self.prepared_verifying_key, &proof, &public_inputs)?;
+        Ok(success)
+    }
+
+    /// Verifies a signal proof and that it was made for `signal`.
+    ///
+    /// The epoch is not checked against the local clock: there is no clock on
+    /// wasm, and proofs of past epochs must still verify when they are kept as
+    /// evidence for slashing. Relays use `verify_signal_at` to also bound the epoch.
+    pub fn verify_signal(&self, message: &RLNProof<E>, signal: &[u8]) -> Result<bool, RlnError> {
+        if message.share_x != hash_signal::<E>(signal)
+            || message.rln_identifier != self.rln_identifier
+        {
+            return Ok(false);
+        }
+        let success = verify_proof(
+            &self.prepared_verifying_key,
+            &message.proof,
+            &message.public_inputs(),
+        )?;
+        Ok(success)
+    }
+
+    /// Verifies a signal proof as `verify_signal` does, rejecting epochs
+    /// further than the allowed skew from the local time `now`.
+    pub fn verify_signal_at(
+        &self,
+        message: &RLNProof<E>,
+        signal: &[u8],
+        epochs: &EpochConfig,
+        now: u64,
+    ) -> Result<bool, RlnError> {
+        if !epochs.is_within_skew::<E>(message.epoch, now) {
+            return Ok(false);
+        }
+        self.verify_signal(message, signal)
+    }
+
+    /// Verifies many proofs with a single multi-pairing, returns the validity of each proof.
+    /// Proofs are combined with fresh random scalars and a failing batch
+    /// is split until the invalid proofs are found.
+    pub fn verify_batch(&self, proofs: &[(Proof<E>, Vec<E::Fr>)]) -> Result<Vec<bool>, RlnError> {
+        let mut rng = secure_rng()?;
+        // malformed proofs and proofs of other applications are invalid without pairing them
+        let (scoped, foreign): (Vec<_>, Vec<_>) = (0..proofs.len()).partition(|i| {
+            let public_inputs = &proofs[*i].1;
+            public_inputs.len() + 1 == self.verifying_key.ic.len()
+                && public_inputs.get(5) == Some(&self.rln_identifier)
+        });
+        if foreign.is_empty() {
+            return batch::verify_batch(&self.verifying_key, proofs, &mut rng);
+        }
+        let scoped_proofs: Vec<_> = scoped.iter().map(|i| proofs[*i].clone()).collect();
+        let scoped_results = batch::verify_batch(&self.verifying_key, &scoped_proofs, &mut rng)?;
+        let mut results = vec![false; proofs.len()];
+        for (i, valid) in scoped.into_iter().zip(scoped_results) {
+            results[i] = valid;
+        }
+        Ok(results)
+    }
+
+    /// Batch verification over `n` concatenated proofs each followed by its public inputs,
+    /// writes one byte per proof, 1 if valid and 0 if not.
+    pub fn verify_batch_raw<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        n: usize,
+        mut output: W,
+    ) -> Result<(), RlnError> {
+        let mut proofs = Vec::with_capacity(n);
+        for _ in 0..n {
+            let message = RLNProof::<E>::read(&mut input)?;
+            let public_inputs = message.public_inputs();
+            proofs.push((message.proof, public_inputs));
+        }
+        let results = self.verify_batch(&proofs)?;
+        let raw_results: Vec<u8> = results.into_iter().map(|valid| valid as u8).collect();
+        output.write_all(&raw_results)?;
+        Ok(())
+    }
+
+    pub fn export_verifier_key<W: Write>(&self, w: W) -> Result<(), RlnError> {
+        Ok(self.verifying_key.write(w)?)
+    }
+}
+
+// Prover of the application, every hash of the circuit is taken by H
+// and the membership tree is expected to use its native hasher.
+#[cfg(feature = "prover")]
+pub struct RLN<E, H = PoseidonCircuit<E>>
+where
+    E: Engine,
+    H: HasherGadget<E>,
 {
     circuit_parameters: Parameters<E>,
-    merkle_depth: usize
+    verifier: RLNVerifier<E>,
+    merkle_depth: usize,
+    hasher: H,
 }
 
+#[cfg(feature = "prover")]
 impl<E> RLN<E>
 where
     E: Engine,
 {
+    /// RLN instance of the application tagged by `rln_identifier`.
+    pub fn new(merkle_depth: usize, rln_identifier: E::Fr) -> Result<RLN<E>, RlnError> {
+        Self::new_with_hasher(merkle_depth, rln_identifier, PoseidonCircuit::new())
+    }
 
-    fn new_circuit(merkle_depth: usize) -> Parameters<E> {
+    pub fn new_with_raw_params<R: Read>(
+        merkle_depth: usize,
+        raw_circuit_parameters: R,
+        rln_identifier: E::Fr,
+    ) -> Result<RLN<E>, RlnError> {
+        Self::new_with_hasher_and_raw_params(
+            merkle_depth,
+            raw_circuit_parameters,
+            rln_identifier,
+            PoseidonCircuit::new(),
+        )
+    }
+}
+
+#[cfg(feature = "prover")]
+impl<E, H> RLN<E, H>
+where
+    E: Engine,
+    H: HasherGadget<E>,
+{
+    fn new_circuit(merkle_depth: usize, hasher: H) -> Result<Parameters<E>, RlnError> {
         let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
         let inputs = RLNInputs::<E>::empty(merkle_depth);
-        let circuit = RLNCircuit::<E> {
-            inputs,
-            hasher: PoseidonCircuit::<E>::new(),
-        };
-        generate_random_parameters(circuit, &mut rng).unwrap()
+        let circuit = RLNCircuit { inputs, hasher };
+        Ok(generate_random_parameters(circuit, &mut rng)?)
     }
 
     fn new_with_params(
         merkle_depth: usize,
         circuit_parameters: Parameters<E>,
-    ) -> RLN<E> {
-        RLN {
+        rln_identifier: E::Fr,
+        hasher: H,
+    ) -> Result<RLN<E, H>, RlnError> {
+        let verifier = RLNVerifier::new(circuit_parameters.vk.clone(), rln_identifier)?;
+        Ok(RLN {
             circuit_parameters,
-            merkle_depth
-        }
+            verifier,
+            merkle_depth,
+            hasher,
+        })
     }
 
-    pub fn new(merkle_depth: usize) -> RLN<E> {
-
-        let circuit_parameters = Self::new_circuit(merkle_depth);
-        Self::new_with_params(merkle_depth, circuit_parameters)
+    /// RLN instance whose circuit hashes with `hasher`.
+    pub fn new_with_hasher(
+        merkle_depth: usize,
+        rln_identifier: E::Fr,
+        hasher: H,
+    ) -> Result<RLN<E, H>, RlnError> {
+        let circuit_parameters = Self::new_circuit(merkle_depth, hasher.clone())?;
+        Self::new_with_params(merkle_depth, circuit_parameters, rln_identifier, hasher)
     }
 
-    pub fn new_with_raw_params<R: Read>(
+    /// Parameters must come from a circuit built with the same hasher.
+    pub fn new_with_hasher_and_raw_params<R: Read>(
         merkle_depth: usize,
-        raw_circuit_parameters: R
-    ) -> io::Result<RLN<E>> {
+        raw_circuit_parameters: R,
+        rln_identifier: E::Fr,
+        hasher: H,
+    ) -> Result<RLN<E, H>, RlnError> {
         let circuit_parameters = Parameters::<E>::read(raw_circuit_parameters, true)?;
 
-        Ok(Self::new_with_params(
-            merkle_depth,
-            circuit_parameters
-        ))
+        Self::new_with_params(merkle_depth, circuit_parameters, rln_identifier, hasher)
     }
 
-    pub fn hasher(&self) -> PoseidonHasher<E> {
-        PoseidonHasher::<E>::new()
+    pub fn rln_identifier(&self) -> E::Fr {
+        self.verifier.rln_identifier()
     }
 
-    pub fn hash<R: Read, W: Write>(&self, input: R, n: usize, mut output: W) -> io::Result<()> {
-        let mut hasher = self.hasher();
+    pub fn hasher(&self) -> H::Native {
+        self.hasher.native()
+    }
+
+    pub fn hash<R: Read, W: Write>(
+        &self,
+        input: R,
+        n: usize,
+        mut output: W,
+    ) -> Result<(), RlnError> {
+        let hasher = self.hasher();
         let input: Vec<E::Fr> = read_inputs::<R, E>(input, n)?;
-        let result = hasher.hash(input);
+        let result = hasher.hash(input)?;
         // let mut output_data: Vec<u8> = Vec::new();
         result.into_repr().write_le(&mut output)?;
         Ok(())
     }
 
-    pub fn generate_proof<R: Read, W: Write>(&self, input: R, mut output: W) -> io::Result<()> {
-        use rand::chacha::ChaChaRng;
-        use rand::SeedableRng;
-        let mut rng = ChaChaRng::new_unseeded();
+    /// Writes the field element a signal maps to, which is the share_x of its proof.
+    pub fn hash_signal<W: Write>(&self, signal: &[u8], mut output: W) -> Result<(), RlnError> {
+        hash_signal::<E>(signal).into_repr().write_le(&mut output)?;
+        Ok(())
+    }
+
+    /// Generates a proof blinded with fresh randomness from the operating system.
+    pub fn generate_proof<R: Read, W: Write>(&self, input: R, output: W) -> Result<(), RlnError> {
+        let mut rng = secure_rng()?;
+        self.generate_proof_with_rng(input, output, &mut rng)
+    }
+
+    /// Generates a proof drawing the Groth16 blinding factors from `rng`.
+    /// A seeded generator makes proofs reproducible, so use it only in tests.
+    pub fn generate_proof_with_rng<R: Read, W: Write, G: Rng>(
+        &self,
+        input: R,
+        mut output: W,
+        rng: &mut G,
+    ) -> Result<(), RlnError> {
         let inputs = RLNInputs::<E>::read(input)?;
-        assert_eq!(self.merkle_depth, inputs.merkle_depth());
-        let circuit_hasher = PoseidonCircuit::<E>::new();
-        let circuit = RLNCircuit {
-            inputs: inputs.clone(),
-            hasher: circuit_hasher.clone(),
-        };
-        let proof = create_random_proof(circuit, &self.circuit_parameters, &mut rng).unwrap();
+        let proof = self.prove(inputs, rng)?;
         write_uncompressed_proof(proof, &mut output)?;
+        Ok(())
+    }
+
+    /// Proves that the member at `leaf_index` sends `signal` in `epoch`.
+    /// The share and the nullifier are derived from the identity secret,
+    /// with share_x being the hash of the signal.
+    pub fn prove_signal<T: MembershipTree<E, Hasher = H::Native>>(
+        &self,
+        identity_secret: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+        epoch: E::Fr,
+        signal: &[u8],
+    ) -> Result<RLNProof<E>, RlnError> {
+        let share_x = hash_signal::<E>(signal);
+        let inputs = RLNInputs::<E>::from_identity(
+            identity_secret,
+            membership_tree,
+            leaf_index,
+            epoch,
+            self.rln_identifier(),
+            share_x,
+        )?;
+        let public_inputs = inputs.public_inputs()?;
+        let mut rng = secure_rng()?;
+        let proof = self.prove(inputs, &mut rng)?;
+        Ok(RLNProof {
+            proof,
+            root: public_inputs[0],
+            epoch: public_inputs[1],
+            share_x: public_inputs[2],
+            share_y: public_inputs[3],
+            nullifier: public_inputs[4],
+            rln_identifier: public_inputs[5],
+        })
+    }
+
+    /// Proves a signal in the epoch containing the unix time `timestamp`.
+    pub fn prove_signal_at<T: MembershipTree<E, Hasher = H::Native>>(
+        &self,
+        identity_secret: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+        epochs: &EpochConfig,
+        timestamp: u64,
+        signal: &[u8],
+    ) -> Result<RLNProof<E>, RlnError> {
+        let epoch = epochs.epoch_at::<E>(timestamp);
+        self.prove_signal(identity_secret, membership_tree, leaf_index, epoch, signal)
+    }
+
+    /// Verifies a signal proof, see `RLNVerifier::verify_signal` for why the epoch is not checked.
+    pub fn verify_signal(&self, message: &RLNProof<E>, signal: &[u8]) -> Result<bool, RlnError> {
+        self.verifier.verify_signal(message, signal)
+    }
 
-        // proof.write(&mut w).unwrap();
+    /// Verifies a signal proof, see `RLNVerifier::verify_signal_at`.
+    pub fn verify_signal_at(
+        &self,
+        message: &RLNProof<E>,
+        signal: &[u8],
+        epochs: &EpochConfig,
+        now: u64,
+    ) -> Result<bool, RlnError> {
+        self.verifier.verify_signal_at(message, signal, epochs, now)
+    }
+
+    /// Recovers the secret of a member from two shares it revealed under one nullifier
+    /// in `epoch` of this application. Fails if the shares are on the same point,
+    /// do not open `nullifier`, or if the secret is not the member at `leaf_index`
+    /// of `membership_tree`.
+    pub fn recover_id_secret<T: MembershipTree<E, Hasher = H::Native>>(
+        &self,
+        share_1: (E::Fr, E::Fr),
+        share_2: (E::Fr, E::Fr),
+        epoch: E::Fr,
+        nullifier: E::Fr,
+        membership_tree: &T,
+        leaf_index: usize,
+    ) -> Result<E::Fr, RlnError> {
+        let hasher = self.hasher();
+        let (a_0, a_1) = interpolate_line::<E>(share_1, share_2)?;
+        let external_nullifier = hasher.hash(vec![epoch, self.rln_identifier()])?;
+        if hasher.hash(vec![a_0, external_nullifier])? != a_1
+            || hasher.hash(vec![a_1])? != nullifier
+        {
+            return Err(RlnError::InvalidShares("shares do not open the nullifier"));
+        }
+        let witness = membership_tree.witness(leaf_index)?;
+        if !membership_tree.check_inclusion(witness, leaf_index, a_0)? {
+            return Err(RlnError::InvalidShares("recovered secret is not a member"));
+        }
+        Ok(a_0)
+    }
+
+    /// Recovers the secret of a member from the public inputs of two of its proofs
+    /// in the same epoch of this application and writes it to `output`.
+    /// `raw_membership` holds the expected root followed by the authentication path
+    /// of the member, encoded as in `RLNInputs::encode_auth_path`. Both proofs must be
+    /// made under that root and the commitment of the secret must be the leaf of the path.
+    pub fn recover_id_secret_from_public_inputs<R: Read, W: Write>(
+        &self,
+        raw_public_inputs_1: R,
+        raw_public_inputs_2: R,
+        mut raw_membership: R,
+        mut output: W,
+    ) -> Result<(), RlnError> {
+        let hasher = self.hasher();
+        let public_inputs_1 = RLNInputs::<E>::read_public_inputs(raw_public_inputs_1)?;
+        let public_inputs_2 = RLNInputs::<E>::read_public_inputs(raw_public_inputs_2)?;
+        if public_inputs_1[5] != self.rln_identifier()
+            || public_inputs_2[5] != self.rln_identifier()
+        {
+            return Err(RlnError::RlnIdentifierMismatch);
+        }
+        let (epoch, nullifier) = (public_inputs_1[1], public_inputs_1[4]);
+        if epoch != public_inputs_2[1] || nullifier != public_inputs_2[4] {
+            return Err(RlnError::InvalidShares("different epoch or nullifier"));
+        }
+        let root = read_fr::<_, E>(&mut raw_membership)?;
+        let auth_path = RLNInputs::<E>::decode_auth_path(&mut raw_membership)?;
+        if public_inputs_1[0] != public_inputs_2[0] {
+            return Err(RlnError::InvalidShares("different roots"));
+        }
+        if public_inputs_1[0] != root {
+            return Err(RlnError::InvalidShares(
+                "proofs are not made under the expected root",
+            ));
+        }
+        if auth_path.len() != self.merkle_depth {
+            return Err(RlnError::DepthMismatch {
+                expected: self.merkle_depth,
+                actual: auth_path.len(),
+            });
+        }
+        let share_1 = (public_inputs_1[2], public_inputs_1[3]);
+        let share_2 = (public_inputs_2[2], public_inputs_2[3]);
+        let (a_0, a_1) = interpolate_line::<E>(share_1, share_2)?;
+        let external_nullifier = hasher.hash(vec![epoch, self.rln_identifier()])?;
+        if hasher.hash(vec![a_0, external_nullifier])? != a_1
+            || hasher.hash(vec![a_1])? != nullifier
+        {
+            return Err(RlnError::InvalidShares("shares do not open the nullifier"));
+        }
+        let auth_path = auth_path.into_iter().flatten().collect();
+        if witness_root(&hasher, hasher.hash(vec![a_0])?, auth_path)? != root {
+            return Err(RlnError::InvalidShares("recovered secret is not a member"));
+        }
+        a_0.into_repr().write_le(&mut output)?;
         Ok(())
     }
 
-    pub fn verify<R: Read>(&self, uncompresed_proof: R, raw_public_inputs: R) -> io::Result<bool> {
-        let proof = read_uncompressed_proof(uncompresed_proof)?;
-        // let proof = Proof::read(uncompresed_proof).unwrap();
-        let public_inputs = RLNInputs::<E>::read_public_inputs(raw_public_inputs)?;
-        let verifing_key = prepare_verifying_key(&self.circuit_parameters.vk);
-        let success = verify_proof(&verifing_key, &proof, &public_inputs).unwrap();
-        Ok(success)
+    fn prove<G: Rng>(&self, inputs: RLNInputs<E>, rng: &mut G) -> Result<Proof<E>, RlnError> {
+        if self.merkle_depth != inputs.merkle_depth() {
+            return Err(RlnError::DepthMismatch {
+                expected: self.merkle_depth,
+                actual: inputs.merkle_depth(),
+            });
+        }
+        if inputs.rln_identifier != Some(self.rln_identifier()) {
+            return Err(RlnError::RlnIdentifierMismatch);
+        }
+        inputs.check_witness(&self.hasher())?;
+        let circuit = RLNCircuit {
+            inputs,
+            hasher: self.hasher.clone(),
+        };
+        Ok(create_random_proof(circuit, &self.circuit_parameters, rng)?)
     }
 
-    pub fn key_gen<W: Write>(&self, mut w: W) -> io::Result<()> {
-        let mut rng = XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
-        let mut hasher = self.hasher();
-        let secret = E::Fr::rand(&mut rng);
-        let public: E::Fr = hasher.hash(vec![secret.clone()]);
+    pub fn verify<R: Read>(
+        &self,
+        uncompresed_proof: R,
+        raw_public_inputs: R,
+    ) -> Result<bool, RlnError> {
+        self.verifier.verify(uncompresed_proof, raw_public_inputs)
+    }
+
+    /// Verifies many proofs with a single multi-pairing, see `RLNVerifier::verify_batch`.
+    pub fn verify_batch(&self, proofs: &[(Proof<E>, Vec<E::Fr>)]) -> Result<Vec<bool>, RlnError> {
+        self.verifier.verify_batch(proofs)
+    }
+
+    /// Batch verification over raw proofs, see `RLNVerifier::verify_batch_raw`.
+    pub fn verify_batch_raw<R: Read, W: Write>(
+        &self,
+        input: R,
+        n: usize,
+        output: W,
+    ) -> Result<(), RlnError> {
+        self.verifier.verify_batch_raw(input, n, output)
+    }
+
+    pub fn verifier(&self) -> &RLNVerifier<E> {
+        &self.verifier
+    }
+
+    /// Writes a fresh identity secret and its commitment,
+    /// drawing the secret from the operating system entropy source.
+    pub fn key_gen<W: Write>(&self, w: W) -> Result<(), RlnError> {
+        let mut rng = secure_rng()?;
+        self.key_gen_with_rng(&mut rng, w)
+    }
+
+    /// Deterministic key generation, the same seed always gives the same keypair.
+    /// Meant for test fixtures, use `key_gen` for real identities.
+    pub fn key_gen_with_seed<W: Write>(&self, seed: &[u8], w: W) -> Result<(), RlnError> {
+        let mut rng = seeded_rng(seed);
+        self.key_gen_with_rng(&mut rng, w)
+    }
+
+    /// Key generation with a caller supplied generator,
+    /// which must be a CSPRNG for identities used in production.
+    pub fn key_gen_with_rng<G: Rng, W: Write>(
+        &self,
+        rng: &mut G,
+        mut w: W,
+    ) -> Result<(), RlnError> {
+        let hasher = self.hasher();
+        let secret = E::Fr::rand(rng);
+        let public: E::Fr = hasher.hash(vec![secret])?;
         secret.into_repr().write_le(&mut w)?;
         public.into_repr().write_le(&mut w)?;
         Ok(())
     }
 
-    pub fn export_verifier_key<W: Write>(&self, w: W) -> io::Result<()> {
-        self.circuit_parameters.vk.write(w)
+    pub fn export_verifier_key<W: Write>(&self, w: W) -> Result<(), RlnError> {
+        Ok(self.circuit_parameters.vk.write(w)?)
+    }
+
+    pub fn export_circuit_parameters<W: Write>(&self, w: W) -> Result<(), RlnError> {
+        Ok(self.circuit_parameters.write(w)?)
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod test {
+    use super::{RLNProof, RLNVerifier, RLN};
+    use crate::circuit::rln::RLNInputs;
+    use crate::merkle::MerkleTree;
+    use crate::poseidon::Poseidon as PoseidonHasher;
+    use rand::{Rand, SeedableRng, XorShiftRng};
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    use sapling_crypto::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+
+    const MERKLE_DEPTH: usize = 3;
+
+    fn rln_identifier() -> Fr {
+        Fr::from_str("100").unwrap()
     }
 
-    pub fn export_circuit_parameters<W: Write>(&self, w: W) -> io::Result<()> {
-        self.circuit_parameters.write(w)
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654])
+    }
+
+    // an RLN instance and a tree with a fresh member at each of the leaf indices
+    fn setup(leaf_indices: &[usize]) -> (RLN<Bn256>, XorShiftRng, MerkleTree<Bn256>, Vec<Fr>) {
+        let rln = RLN::<Bn256>::new(MERKLE_DEPTH, rln_identifier()).unwrap();
+        let mut rng = rng();
+        let hasher = PoseidonHasher::<Bn256>::new();
+        let mut membership_tree = MerkleTree::empty(hasher.clone(), MERKLE_DEPTH).unwrap();
+        let id_keys: Vec<Fr> = leaf_indices
+            .iter()
+            .map(|leaf_index| {
+                let id_key = Fr::rand(&mut rng);
+                membership_tree
+                    .update(*leaf_index, hasher.hash(vec![id_key]).unwrap())
+                    .unwrap();
+                id_key
+            })
+            .collect();
+        (rln, rng, membership_tree, id_keys)
+    }
+
+    #[test]
+    fn test_prove_signal() {
+        let leaf_index = 5;
+        let (rln, mut rng, membership_tree, id_keys) = setup(&[leaf_index]);
+        let id_key = id_keys[0];
+
+        let epoch = Fr::rand(&mut rng);
+        let signal = b"hello world";
+        let message = rln
+            .prove_signal(id_key, &membership_tree, leaf_index, epoch, signal)
+            .unwrap();
+        assert_eq!(message.root, membership_tree.root());
+        assert_eq!(message.epoch, epoch);
+        assert!(rln.verify_signal(&message, signal).unwrap());
+        assert!(!rln.verify_signal(&message, b"another signal").unwrap());
+
+        let mut raw_message: Vec<u8> = Vec::new();
+        message.write(&mut raw_message).unwrap();
+        let restored = RLNProof::<Bn256>::read(raw_message.as_slice()).unwrap();
+        assert_eq!(restored.public_inputs(), message.public_inputs());
+        assert!(rln.verify_signal(&restored, signal).unwrap());
+
+        // a member that is not in the tree can not signal
+        assert!(rln
+            .prove_signal(id_key, &membership_tree, leaf_index + 1, epoch, signal)
+            .is_err());
+    }
+
+    #[test]
+    fn test_recover_id_secret() {
+        let leaf_index = 2;
+        let (rln, mut rng, membership_tree, id_keys) = setup(&[leaf_index]);
+        let id_key = id_keys[0];
+
+        let epoch = Fr::rand(&mut rng);
+        let message_1 = rln
+            .prove_signal(id_key, &membership_tree, leaf_index, epoch, b"first")
+            .unwrap();
+        let message_2 = rln
+            .prove_signal(id_key, &membership_tree, leaf_index, epoch, b"second")
+            .unwrap();
+        assert_eq!(message_1.nullifier, message_2.nullifier);
+
+        let share_1 = (message_1.share_x, message_1.share_y);
+        let share_2 = (message_2.share_x, message_2.share_y);
+        let recover_shares = |share_2, epoch, tree: &MerkleTree<Bn256>, leaf_index| {
+            rln.recover_id_secret(
+                share_1,
+                share_2,
+                epoch,
+                message_1.nullifier,
+                tree,
+                leaf_index,
+            )
+        };
+        let recovered = recover_shares(share_2, epoch, &membership_tree, leaf_index).unwrap();
+        assert_eq!(recovered, id_key);
+
+        let mut raw_public_inputs_1: Vec<u8> = Vec::new();
+        let mut raw_public_inputs_2: Vec<u8> = Vec::new();
+        for input in message_1.public_inputs().iter() {
+            input
+                .into_repr()
+                .write_le(&mut raw_public_inputs_1)
+                .unwrap();
+        }
+        for input in message_2.public_inputs().iter() {
+            input
+                .into_repr()
+                .write_le(&mut raw_public_inputs_2)
+                .unwrap();
+        }
+        let membership = |tree: &MerkleTree<Bn256>, leaf_index: usize| {
+            let mut raw_membership: Vec<u8> = Vec::new();
+            tree.root()
+                .into_repr()
+                .write_le(&mut raw_membership)
+                .unwrap();
+            let auth_path = tree.witness(leaf_index).unwrap().into_iter().map(Some);
+            RLNInputs::<Bn256>::encode_auth_path(&mut raw_membership, auth_path.collect()).unwrap();
+            raw_membership
+        };
+        let recover = |raw_public_inputs_2: &[u8], raw_membership: &[u8]| {
+            let mut raw_secret: Vec<u8> = Vec::new();
+            rln.recover_id_secret_from_public_inputs(
+                raw_public_inputs_1.as_slice(),
+                raw_public_inputs_2,
+                raw_membership,
+                &mut raw_secret,
+            )
+            .map(|_| raw_secret)
+        };
+        let mut expected: Vec<u8> = Vec::new();
+        id_key.into_repr().write_le(&mut expected).unwrap();
+        let raw_membership = membership(&membership_tree, leaf_index);
+        assert_eq!(
+            recover(&raw_public_inputs_2, &raw_membership).unwrap(),
+            expected
+        );
+        // the path of another leaf does not open to the secret
+        assert!(recover(&raw_public_inputs_2, &membership(&membership_tree, 0)).is_err());
+        // proofs under another root
+        let mut other_root = raw_public_inputs_2.clone();
+        let root = Fr::from_str("1").unwrap();
+        root.into_repr().write_le(&mut other_root[..32]).unwrap();
+        assert!(recover(&other_root, &raw_membership).is_err());
+
+        // equal share_x
+        assert!(recover_shares(share_1, epoch, &membership_tree, leaf_index).is_err());
+        // shares of another epoch
+        let other_epoch = Fr::rand(&mut rng);
+        assert!(recover_shares(share_2, other_epoch, &membership_tree, leaf_index).is_err());
+        // not the member at another leaf or of another tree
+        assert!(recover_shares(share_2, epoch, &membership_tree, 0).is_err());
+        let other_tree = MerkleTree::empty(PoseidonHasher::<Bn256>::new(), MERKLE_DEPTH).unwrap();
+        assert!(recover_shares(share_2, epoch, &other_tree, leaf_index).is_err());
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let (rln, mut rng, membership_tree, id_keys) = setup(&[0, 1, 2, 3, 4]);
+        let epoch = Fr::rand(&mut rng);
+        let mut proofs: Vec<_> = id_keys
+            .iter()
+            .enumerate()
+            .map(|(i, id_key)| {
+                let message = rln
+                    .prove_signal(*id_key, &membership_tree, i, epoch, b"signal")
+                    .unwrap();
+                let public_inputs = message.public_inputs();
+                (message.proof, public_inputs)
+            })
+            .collect();
+        assert_eq!(rln.verify_batch(&proofs).unwrap(), vec![true; 5]);
+
+        let mut raw_proofs: Vec<u8> = Vec::new();
+        for (proof, public_inputs) in proofs.iter() {
+            let message = RLNProof::<Bn256> {
+                proof: proof.clone(),
+                root: public_inputs[0],
+                epoch: public_inputs[1],
+                share_x: public_inputs[2],
+                share_y: public_inputs[3],
+                nullifier: public_inputs[4],
+                rln_identifier: public_inputs[5],
+            };
+            message.write(&mut raw_proofs).unwrap();
+        }
+        let mut raw_results: Vec<u8> = Vec::new();
+        rln.verify_batch_raw(raw_proofs.as_slice(), 5, &mut raw_results)
+            .unwrap();
+        assert_eq!(raw_results, vec![1u8; 5]);
+
+        // tamper with two of them
+        proofs[1].1[3] = Fr::rand(&mut rng);
+        proofs[4].1[4] = Fr::rand(&mut rng);
+        assert_eq!(
+            rln.verify_batch(&proofs).unwrap(),
+            vec![true, false, true, true, false]
+        );
+        // entries with missing or extra public inputs
+        proofs[2].1.pop();
+        proofs[3].1.push(Fr::rand(&mut rng));
+        assert_eq!(
+            rln.verify_batch(&proofs).unwrap(),
+            vec![true, false, false, false, false]
+        );
+        assert_eq!(
+            crate::batch::verify_batch(&rln.verifier.verifying_key, &proofs, &mut rng).unwrap(),
+            vec![true, false, false, false, false]
+        );
+        assert!(rln.verify_batch(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verifier_from_key() {
+        let (rln, mut rng, membership_tree, id_keys) = setup(&[0]);
+        let id_key = id_keys[0];
+        let epoch = Fr::rand(&mut rng);
+        let message = rln
+            .prove_signal(id_key, &membership_tree, 0, epoch, b"signal")
+            .unwrap();
+
+        let mut raw_verifier_key: Vec<u8> = Vec::new();
+        rln.export_verifier_key(&mut raw_verifier_key).unwrap();
+        let verifier = RLNVerifier::<Bn256>::new_with_raw_verifier_key(
+            raw_verifier_key.as_slice(),
+            rln_identifier(),
+        )
+        .unwrap();
+        assert!(verifier.verify_signal(&message, b"signal").unwrap());
+
+        let mut raw_proof: Vec<u8> = Vec::new();
+        let mut raw_public_inputs: Vec<u8> = Vec::new();
+        message.write(&mut raw_proof).unwrap();
+        for input in message.public_inputs().iter() {
+            input.into_repr().write_le(&mut raw_public_inputs).unwrap();
+        }
+        assert!(verifier
+            .verify(raw_proof.as_slice(), raw_public_inputs.as_slice())
+            .unwrap());
+
+        let mut exported: Vec<u8> = Vec::new();
+        verifier.export_verifier_key(&mut exported).unwrap();
+        assert_eq!(exported, raw_verifier_key);
+
+        assert!(RLNVerifier::<Bn256>::new_with_raw_verifier_key(
+            &raw_verifier_key[..100],
+            rln_identifier()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_signal_epochs() {
+        use crate::epoch::EpochConfig;
+        let (rln, _, membership_tree, id_keys) = setup(&[1]);
+        let id_key = id_keys[0];
+
+        let epochs = EpochConfig::new(60, 1).unwrap();
+        let sent_at = 1_700_000_000;
+        let message = rln
+            .prove_signal_at(id_key, &membership_tree, 1, &epochs, sent_at, b"signal")
+            .unwrap();
+        assert_eq!(message.epoch, epochs.epoch_at::<Bn256>(sent_at));
+        assert!(rln
+            .verify_signal_at(&message, b"signal", &epochs, sent_at + 30)
+            .unwrap());
+        assert!(!rln
+            .verify_signal_at(&message, b"signal", &epochs, sent_at + 600)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_rln_identifier() {
+        use crate::error::RlnError;
+        let (rln, mut rng, membership_tree, id_keys) = setup(&[0]);
+        let id_key = id_keys[0];
+        let epoch = Fr::rand(&mut rng);
+
+        // the same circuit parameters serving another application
+        let mut raw_circuit_parameters: Vec<u8> = Vec::new();
+        rln.export_circuit_parameters(&mut raw_circuit_parameters)
+            .unwrap();
+        let other_identifier = Fr::from_str("101").unwrap();
+        let other = RLN::<Bn256>::new_with_raw_params(
+            MERKLE_DEPTH,
+            raw_circuit_parameters.as_slice(),
+            other_identifier,
+        )
+        .unwrap();
+
+        let message = rln
+            .prove_signal(id_key, &membership_tree, 0, epoch, b"signal")
+            .unwrap();
+        let other_message = other
+            .prove_signal(id_key, &membership_tree, 0, epoch, b"signal")
+            .unwrap();
+        assert_eq!(message.rln_identifier, rln_identifier());
+        assert_ne!(message.nullifier, other_message.nullifier);
+        assert!(rln.verify_signal(&message, b"signal").unwrap());
+        assert!(other.verify_signal(&other_message, b"signal").unwrap());
+        assert!(!rln.verify_signal(&other_message, b"signal").unwrap());
+        assert!(!other.verify_signal(&message, b"signal").unwrap());
+
+        let mut raw_proof: Vec<u8> = Vec::new();
+        let mut raw_public_inputs: Vec<u8> = Vec::new();
+        other_message.write(&mut raw_proof).unwrap();
+        for input in other_message.public_inputs().iter() {
+            input.into_repr().write_le(&mut raw_public_inputs).unwrap();
+        }
+        assert!(!rln
+            .verify(raw_proof.as_slice(), raw_public_inputs.as_slice())
+            .unwrap());
+
+        let proofs = vec![
+            (message.proof.clone(), message.public_inputs()),
+            (other_message.proof.clone(), other_message.public_inputs()),
+        ];
+        assert_eq!(rln.verify_batch(&proofs).unwrap(), vec![true, false]);
+
+        // raw inputs for another application are refused by the prover
+        let inputs = crate::circuit::rln::RLNInputs::<Bn256>::from_identity(
+            id_key,
+            &membership_tree,
+            0,
+            epoch,
+            other_identifier,
+            Fr::rand(&mut rng),
+        )
+        .unwrap();
+        let mut raw_inputs: Vec<u8> = Vec::new();
+        inputs.write(&mut raw_inputs).unwrap();
+        match rln.generate_proof(raw_inputs.as_slice(), &mut Vec::new()) {
+            Err(RlnError::RlnIdentifierMismatch) => (),
+            _ => panic!("expected rln identifier mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_generic_hasher() {
+        use crate::circuit::poseidon2::Poseidon2Circuit;
+        use crate::poseidon2::Poseidon2;
+
+        let circuit_hasher = Poseidon2Circuit::<Bn256>::new();
+        let rln = RLN::new_with_hasher(MERKLE_DEPTH, rln_identifier(), circuit_hasher).unwrap();
+        let mut rng = rng();
+        let hasher = Poseidon2::<Bn256>::new();
+
+        let id_key = Fr::rand(&mut rng);
+        let mut membership_tree = MerkleTree::empty(hasher.clone(), MERKLE_DEPTH).unwrap();
+        membership_tree
+            .update(4, hasher.hash(vec![id_key]).unwrap())
+            .unwrap();
+
+        let epoch = Fr::rand(&mut rng);
+        let message_1 = rln
+            .prove_signal(id_key, &membership_tree, 4, epoch, b"first")
+            .unwrap();
+        assert!(rln.verify_signal(&message_1, b"first").unwrap());
+
+        let message_2 = rln
+            .prove_signal(id_key, &membership_tree, 4, epoch, b"second")
+            .unwrap();
+        let recovered = rln
+            .recover_id_secret(
+                (message_1.share_x, message_1.share_y),
+                (message_2.share_x, message_2.share_y),
+                epoch,
+                message_1.nullifier,
+                &membership_tree,
+                4,
+            )
+            .unwrap();
+        assert_eq!(recovered, id_key);
+
+        // the circuit of the default hasher does not accept the proofs
+        let poseidon_rln = RLN::<Bn256>::new(MERKLE_DEPTH, rln_identifier()).unwrap();
+        assert!(!poseidon_rln.verify_signal(&message_1, b"first").unwrap());
     }
 }
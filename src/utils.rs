@@ -2,17 +2,152 @@ use bellman::groth16::Proof;
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use bellman::pairing::{CurveAffine, EncodedPoint, Engine};
 
-use rand::{Rand, SeedableRng, XorShiftRng};
+use crate::error::RlnError;
+use blake2::{Blake2b, Blake2s, Digest};
+use rand::chacha::ChaChaRng;
+use rand::{Rand, Rng, SeedableRng, XorShiftRng};
 use std::io::{self, Error, ErrorKind, Read, Write};
 
-pub fn read_inputs<R: Read, E: Engine>(mut reader: R, n: usize) -> io::Result<Vec<E::Fr>> {
-    let mut out: Vec<E::Fr> = Vec::new();
+// ChaCha generator keyed with the blake2s digest of the seed,
+// so seeds of any length give a full 256 bit key.
+pub fn seeded_rng(seed: &[u8]) -> ChaChaRng {
+    let digest = Blake2s::digest(seed);
+    let key: Vec<u32> = digest
+        .chunks(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    ChaChaRng::from_seed(&key)
+}
+
+// ChaCha generator keyed from the operating system entropy source.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn secure_rng() -> io::Result<ChaChaRng> {
+    let mut os_rng = rand::OsRng::new()?;
+    let key: Vec<u32> = (0..8).map(|_| os_rng.next_u32()).collect();
+    Ok(ChaChaRng::from_seed(&key))
+}
+
+// ChaCha generator keyed from the host's web crypto, browser or node.
+#[cfg(target_arch = "wasm32")]
+pub fn secure_rng() -> io::Result<ChaChaRng> {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    let key: Vec<u32> = seed
+        .chunks(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    Ok(ChaChaRng::from_seed(&key))
+}
+
+pub fn read_fr<R: Read, E: Engine>(mut reader: R) -> Result<E::Fr, RlnError> {
     let mut buf = <E::Fr as PrimeField>::Repr::default();
+    buf.read_le(&mut reader)?;
+    Ok(E::Fr::from_repr(buf)?)
+}
+
+// domain tag of signal hashes, changing it changes every share_x
+pub const SIGNAL_DOMAIN: &[u8] = b"rln-signal-v1";
+
+// blake2b of the length prefixed domain and the message,
+// the 512 bit digest read as little endian and reduced mod r,
+// domains are at most 255 bytes so that the length prefix is one byte
+pub fn hash_to_field<E: Engine>(domain: &[u8], message: &[u8]) -> Result<E::Fr, RlnError> {
+    if domain.len() > u8::MAX as usize {
+        return Err(RlnError::InvalidDomain(domain.len()));
+    }
+    let mut hasher = Blake2b::new();
+    hasher.input([domain.len() as u8]);
+    hasher.input(domain);
+    hasher.input(message);
+    let digest = hasher.result();
+
+    // 2^128 in the field
+    let mut shift = E::Fr::one();
+    shift.double();
+    for _ in 0..7 {
+        shift.square();
+    }
+
+    let mut acc = E::Fr::zero();
+    for chunk in digest.chunks(16).rev() {
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        repr.as_mut()[0] = u64_le(&chunk[..8]);
+        repr.as_mut()[1] = u64_le(&chunk[8..]);
+        let limb = E::Fr::from_repr(repr).expect("128 bit limb is below the modulus");
+        acc.mul_assign(&shift);
+        acc.add_assign(&limb);
+    }
+    Ok(acc)
+}
+
+fn u64_le(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+pub fn hash_signal<E: Engine>(signal: &[u8]) -> E::Fr {
+    hash_to_field::<E>(SIGNAL_DOMAIN, signal).expect("signal domain fits the length byte")
+}
+
+// line a_0 + a_1 * x through two shares, returns (a_0, a_1)
+pub fn interpolate_line<E: Engine>(
+    share_1: (E::Fr, E::Fr),
+    share_2: (E::Fr, E::Fr),
+) -> Result<(E::Fr, E::Fr), RlnError> {
+    let (x_1, y_1) = share_1;
+    let (x_2, y_2) = share_2;
+    let mut dx = x_2;
+    dx.sub_assign(&x_1);
+    let dx_inv = dx
+        .inverse()
+        .ok_or(RlnError::InvalidShares("equal share_x"))?;
+    let mut a_1 = y_2;
+    a_1.sub_assign(&y_1);
+    a_1.mul_assign(&dx_inv);
+    let mut a_0 = a_1;
+    a_0.mul_assign(&x_1);
+    a_0.negate();
+    a_0.add_assign(&y_1);
+    Ok((a_0, a_1))
+}
+
+// lagrange interpolation of the shares evaluated at zero,
+// recovers a_0 of the polynomial of degree shares.len() - 1 through the shares
+pub fn interpolate_at_zero<E: Engine>(shares: &[(E::Fr, E::Fr)]) -> Result<E::Fr, RlnError> {
+    if shares.is_empty() {
+        return Err(RlnError::InvalidShares("no shares"));
+    }
+    let mut a_0 = E::Fr::zero();
+    for (i, (x_i, y_i)) in shares.iter().enumerate() {
+        // l_i(0) = prod_{j != i} x_j / (x_j - x_i)
+        let mut num = E::Fr::one();
+        let mut den = E::Fr::one();
+        for (j, (x_j, _)) in shares.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            num.mul_assign(x_j);
+            let mut dx = *x_j;
+            dx.sub_assign(x_i);
+            den.mul_assign(&dx);
+        }
+        let den_inv = den
+            .inverse()
+            .ok_or(RlnError::InvalidShares("equal share_x"))?;
+        let mut term = *y_i;
+        term.mul_assign(&num);
+        term.mul_assign(&den_inv);
+        a_0.add_assign(&term);
+    }
+    Ok(a_0)
+}
+
+pub fn read_inputs<R: Read, E: Engine>(mut reader: R, n: usize) -> Result<Vec<E::Fr>, RlnError> {
+    let mut out: Vec<E::Fr> = Vec::new();
     for _ in 0..n {
-        buf.read_le(&mut reader)?;
-        let input =
-            E::Fr::from_repr(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        out.push(input);
+        out.push(read_fr::<_, E>(&mut reader)?);
     }
     Ok(out)
 }
@@ -20,14 +155,14 @@ pub fn read_inputs<R: Read, E: Engine>(mut reader: R, n: usize) -> io::Result<Ve
 pub fn write_uncompressed_proof<W: Write, E: Engine>(
     proof: Proof<E>,
     mut writer: W,
-) -> io::Result<()> {
+) -> Result<(), RlnError> {
     writer.write_all(proof.a.into_uncompressed().as_ref())?;
     writer.write_all(proof.b.into_uncompressed().as_ref())?;
     writer.write_all(proof.c.into_uncompressed().as_ref())?;
     Ok(())
 }
 
-pub fn read_uncompressed_proof<R: Read, E: Engine>(mut reader: R) -> io::Result<Proof<E>> {
+pub fn read_uncompressed_proof<R: Read, E: Engine>(mut reader: R) -> Result<Proof<E>, RlnError> {
     let mut g1_repr = <E::G1Affine as CurveAffine>::Uncompressed::empty();
     let mut g2_repr = <E::G2Affine as CurveAffine>::Uncompressed::empty();
 
@@ -78,3 +213,30 @@ pub fn read_uncompressed_proof<R: Read, E: Engine>(mut reader: R) -> io::Result<
 
     Ok(Proof { a, b, c })
 }
+
+#[test]
+fn test_hash_to_field() {
+    use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+    let empty = hash_signal::<Bn256>(b"");
+    let hello = hash_signal::<Bn256>(b"hello world");
+    assert_eq!(
+        empty,
+        Fr::from_str(
+            "4441344200240805420367094334397742816138030655298654240794403561337147125453"
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        hello,
+        Fr::from_str(
+            "758578512921340425948370143402390556809730662870189123256287340113097062590"
+        )
+        .unwrap()
+    );
+    assert_ne!(
+        hello,
+        hash_to_field::<Bn256>(b"another domain", b"hello world").unwrap()
+    );
+    assert!(hash_to_field::<Bn256>(&[0u8; 255], b"hello world").is_ok());
+    assert!(hash_to_field::<Bn256>(&[0u8; 256], b"hello world").is_err());
+}
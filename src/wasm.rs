@@ -1,4 +1,7 @@
+#[cfg(feature = "prover")]
 use crate::public::RLN;
+use crate::public::RLNVerifier;
+use crate::utils::read_fr;
 
 use std::io::{self, Error, ErrorKind, Read, Write};
 use wasm_bindgen::prelude::*;
@@ -17,28 +20,41 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+#[cfg(feature = "prover")]
 #[wasm_bindgen]
 pub struct RLNWasm {
     api: RLN<Bn256>,
 }
 
+#[cfg(feature = "prover")]
 #[wasm_bindgen]
 impl RLNWasm {
     #[wasm_bindgen]
-    pub fn new(merkle_depth: usize) -> RLNWasm {
+    pub fn new(merkle_depth: usize, rln_identifier: &[u8]) -> Result<RLNWasm, JsValue> {
         set_panic_hook();
-        RLNWasm {
-            api: RLN::<Bn256>::new(merkle_depth),
-        }
+        let rln_identifier = match read_fr::<_, Bn256>(rln_identifier) {
+            Ok(rln_identifier) => rln_identifier,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        let api = match RLN::<Bn256>::new(merkle_depth, rln_identifier) {
+            Ok(api) => api,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(RLNWasm { api })
     }
 
     #[wasm_bindgen]
     pub fn new_with_raw_params(
         merkle_depth: usize,
         raw_circuit_parameters: &[u8],
+        rln_identifier: &[u8],
     ) -> Result<RLNWasm, JsValue> {
         set_panic_hook();
-        let api = match RLN::new_with_raw_params(merkle_depth, raw_circuit_parameters) {
+        let rln_identifier = match read_fr::<_, Bn256>(rln_identifier) {
+            Ok(rln_identifier) => rln_identifier,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        let api = match RLN::new_with_raw_params(merkle_depth, raw_circuit_parameters, rln_identifier) {
             Ok(api) => api,
             Err(e) => return Err(e.to_string().into()),
         };
@@ -55,6 +71,26 @@ impl RLNWasm {
         Ok(output)
     }
 
+    #[wasm_bindgen]
+    pub fn verify_batch(&self, raw_proofs: &[u8], n: usize) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.verify_batch_raw(raw_proofs, n, &mut output) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+
+    #[wasm_bindgen]
+    pub fn hash_signal(&self, signal: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.hash_signal(signal, &mut output) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+
     #[wasm_bindgen]
     pub fn verify(
         &self,
@@ -68,6 +104,46 @@ impl RLNWasm {
         Ok(success)
     }
 
+    #[wasm_bindgen]
+    pub fn key_gen(&self) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.key_gen(&mut output) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+
+    #[wasm_bindgen]
+    pub fn key_gen_with_seed(&self, seed: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.key_gen_with_seed(seed, &mut output) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+
+    #[wasm_bindgen]
+    pub fn recover_id_secret(
+        &self,
+        raw_public_inputs_1: &[u8],
+        raw_public_inputs_2: &[u8],
+        raw_membership: &[u8],
+    ) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.recover_id_secret_from_public_inputs(
+            raw_public_inputs_1,
+            raw_public_inputs_2,
+            raw_membership,
+            &mut output,
+        ) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+
     #[wasm_bindgen]
     pub fn export_verifier_key(&self) -> Result<Vec<u8>, JsValue> {
         let mut output: Vec<u8> = Vec::new();
@@ -89,7 +165,55 @@ impl RLNWasm {
     }
 }
 
-#[cfg(test)]
+#[wasm_bindgen]
+pub struct RLNVerifierWasm {
+    api: RLNVerifier<Bn256>,
+}
+
+#[wasm_bindgen]
+impl RLNVerifierWasm {
+    #[wasm_bindgen]
+    pub fn new_with_raw_verifier_key(
+        raw_verifier_key: &[u8],
+        rln_identifier: &[u8],
+    ) -> Result<RLNVerifierWasm, JsValue> {
+        set_panic_hook();
+        let rln_identifier = match read_fr::<_, Bn256>(rln_identifier) {
+            Ok(rln_identifier) => rln_identifier,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        let api = match RLNVerifier::new_with_raw_verifier_key(raw_verifier_key, rln_identifier) {
+            Ok(api) => api,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(RLNVerifierWasm { api })
+    }
+
+    #[wasm_bindgen]
+    pub fn verify(
+        &self,
+        uncompresed_proof: &[u8],
+        raw_public_inputs: &[u8],
+    ) -> Result<bool, JsValue> {
+        let success = match self.api.verify(uncompresed_proof, raw_public_inputs) {
+            Ok(success) => success,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(success)
+    }
+
+    #[wasm_bindgen]
+    pub fn verify_batch(&self, raw_proofs: &[u8], n: usize) -> Result<Vec<u8>, JsValue> {
+        let mut output: Vec<u8> = Vec::new();
+        match self.api.verify_batch_raw(raw_proofs, n, &mut output) {
+            Ok(_) => (),
+            Err(e) => return Err(e.to_string().into()),
+        };
+        Ok(output)
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
 mod test {
 
     use crate::circuit::bench;
@@ -109,18 +233,23 @@ mod test {
         let merkle_depth = 3usize;
         let rln_test = bench::RLNTest::<Bn256>::new(merkle_depth);
 
-        let rln_wasm = super::RLNWasm::new(merkle_depth);
+        let mut rln_identifier: Vec<u8> = Vec::new();
+        bench::RLNTest::<Bn256>::rln_identifier()
+            .into_repr()
+            .write_le(&mut rln_identifier)
+            .unwrap();
+        let rln_wasm = super::RLNWasm::new(merkle_depth, rln_identifier.as_slice()).unwrap();
 
         let mut raw_inputs: Vec<u8> = Vec::new();
         let inputs = rln_test.valid_inputs();
-        inputs.write(&mut raw_inputs);
+        inputs.write(&mut raw_inputs).unwrap();
 
         // let now = Instant::now();
         let proof = rln_wasm.generate_proof(raw_inputs.as_slice()).unwrap();
         // let prover_time = now.elapsed().as_millis() as f64 / 1000.0;
 
         let mut raw_public_inputs: Vec<u8> = Vec::new();
-        inputs.write_public_inputs(&mut raw_public_inputs);
+        inputs.write_public_inputs(&mut raw_public_inputs).unwrap();
 
         assert_eq!(
             rln_wasm
@@ -128,5 +257,120 @@ mod test {
                 .unwrap(),
             true
         );
+
+        // the same member under another application, with the same circuit parameters
+        let hasher = rln_test.hasher();
+        let other_identifier = Fr::from_str("200").unwrap();
+        let mut raw_other_identifier: Vec<u8> = Vec::new();
+        other_identifier
+            .into_repr()
+            .write_le(&mut raw_other_identifier)
+            .unwrap();
+        let circuit_parameters = rln_wasm.export_circuit_parameters().unwrap();
+        let other_wasm = super::RLNWasm::new_with_raw_params(
+            merkle_depth,
+            circuit_parameters.as_slice(),
+            raw_other_identifier.as_slice(),
+        )
+        .unwrap();
+
+        let mut other_inputs = inputs.clone();
+        let external_nullifier = hasher
+            .hash(vec![inputs.epoch.unwrap(), other_identifier])
+            .unwrap();
+        let a_1 = hasher
+            .hash(vec![inputs.id_key.unwrap(), external_nullifier])
+            .unwrap();
+        let mut share_y = a_1;
+        share_y.mul_assign(&inputs.share_x.unwrap());
+        share_y.add_assign(&inputs.id_key.unwrap());
+        other_inputs.rln_identifier = Some(other_identifier);
+        other_inputs.share_y = Some(share_y);
+        other_inputs.nullifier = Some(hasher.hash(vec![a_1]).unwrap());
+
+        let mut raw_other_inputs: Vec<u8> = Vec::new();
+        other_inputs.write(&mut raw_other_inputs).unwrap();
+        assert!(rln_wasm
+            .generate_proof(raw_other_inputs.as_slice())
+            .is_err());
+        let other_proof = other_wasm
+            .generate_proof(raw_other_inputs.as_slice())
+            .unwrap();
+
+        let mut raw_other_public_inputs: Vec<u8> = Vec::new();
+        other_inputs
+            .write_public_inputs(&mut raw_other_public_inputs)
+            .unwrap();
+        assert!(other_wasm
+            .verify(other_proof.as_slice(), raw_other_public_inputs.as_slice())
+            .unwrap());
+        assert!(!rln_wasm
+            .verify(other_proof.as_slice(), raw_other_public_inputs.as_slice())
+            .unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_recover_id_secret_wasm() {
+        let merkle_depth = 3usize;
+        let rln_test = bench::RLNTest::<Bn256>::new(merkle_depth);
+        let hasher = rln_test.hasher();
+        let mut rln_identifier: Vec<u8> = Vec::new();
+        bench::RLNTest::<Bn256>::rln_identifier()
+            .into_repr()
+            .write_le(&mut rln_identifier)
+            .unwrap();
+        let rln_wasm = super::RLNWasm::new(merkle_depth, rln_identifier.as_slice()).unwrap();
+
+        // expected root and the path of the member
+        let inputs = rln_test.valid_inputs();
+        let mut raw_membership: Vec<u8> = Vec::new();
+        inputs
+            .root
+            .unwrap()
+            .into_repr()
+            .write_le(&mut raw_membership)
+            .unwrap();
+        RLNInputs::<Bn256>::encode_auth_path(&mut raw_membership, inputs.auth_path.clone())
+            .unwrap();
+
+        // public inputs of a share of id_key in the epoch of the member
+        let external_nullifier = hasher
+            .hash(vec![inputs.epoch.unwrap(), inputs.rln_identifier.unwrap()])
+            .unwrap();
+        let public_inputs = |id_key: Fr, share_x: Fr| {
+            let a_1 = hasher.hash(vec![id_key, external_nullifier]).unwrap();
+            let mut share_y = a_1;
+            share_y.mul_assign(&share_x);
+            share_y.add_assign(&id_key);
+            let mut share = inputs.clone();
+            share.share_x = Some(share_x);
+            share.share_y = Some(share_y);
+            share.nullifier = Some(hasher.hash(vec![a_1]).unwrap());
+            let mut raw_public_inputs: Vec<u8> = Vec::new();
+            share.write_public_inputs(&mut raw_public_inputs).unwrap();
+            raw_public_inputs
+        };
+
+        let id_key = inputs.id_key.unwrap();
+        let secret = rln_wasm
+            .recover_id_secret(
+                public_inputs(id_key, Fr::one()).as_slice(),
+                public_inputs(id_key, Fr::from_str("2").unwrap()).as_slice(),
+                raw_membership.as_slice(),
+            )
+            .unwrap();
+        let mut expected: Vec<u8> = Vec::new();
+        id_key.into_repr().write_le(&mut expected).unwrap();
+        assert_eq!(secret, expected);
+
+        // shares of a secret that is not in the tree, under the same root
+        let non_member = Fr::from_str("987654321").unwrap();
+        assert!(rln_wasm
+            .recover_id_secret(
+                public_inputs(non_member, Fr::one()).as_slice(),
+                public_inputs(non_member, Fr::from_str("2").unwrap()).as_slice(),
+                raw_membership.as_slice(),
+            )
+            .is_err());
     }
 }
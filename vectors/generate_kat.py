@@ -0,0 +1,149 @@
+#!/usr/bin/env python3
+"""Generates vectors/kat.json, run from the repository root with
+
+    python3 vectors/generate_kat.py > vectors/kat.json
+
+A standalone Python reference shared with no code of the crate: the Poseidon
+constants come from the Grain LFSR of generate_parameters_grain.sage and the
+permutation follows circomlib (x^5, 8 full rounds, capacity first). Its
+Poseidon outputs were checked against circomlibjs and its empty roots against
+the zk-kit incremental merkle tree, the "derived" entries compose those hashes.
+The RLN sections are copied from vectors/zerokit/rln_v1.json and rln_v2.json,
+computed by zerokit with the programs next to them.
+"""
+
+import functools
+import json
+import os
+
+# BN254 scalar field
+P = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+FIELD_BITS = 254
+FULL_ROUNDS = 8
+# partial rounds of circomlib by width
+PARTIAL_ROUNDS = {2: 56, 3: 57, 4: 56, 5: 60, 6: 60, 7: 63, 8: 64, 9: 63}
+
+
+class Grain:
+    def __init__(self, t, rf, rp):
+        bits = []
+        for value, width in [(1, 2), (0, 4), (FIELD_BITS, 12), (t, 12), (rf, 10), (rp, 10)]:
+            bits.extend(int(b) for b in bin(value)[2:].zfill(width))
+        bits.extend([1] * 30)
+        self.state = bits
+        for _ in range(160):
+            self.step()
+
+    def step(self):
+        s = self.state
+        bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0]
+        s.pop(0)
+        s.append(bit)
+        return bit
+
+    # output bits are kept only when the preceding bit is set
+    def next_bit(self):
+        while True:
+            keep = self.step()
+            bit = self.step()
+            if keep:
+                return bit
+
+    def next_int(self):
+        value = 0
+        for _ in range(FIELD_BITS):
+            value = (value << 1) | self.next_bit()
+        return value
+
+
+@functools.lru_cache(maxsize=None)
+def constants(t):
+    rf, rp = FULL_ROUNDS, PARTIAL_ROUNDS[t]
+    grain = Grain(t, rf, rp)
+    round_constants = []
+    for _ in range((rf + rp) * t):
+        while True:
+            r = grain.next_int()
+            if r < P:
+                break
+        round_constants.append(r)
+    while True:
+        samples = [grain.next_int() % P for _ in range(2 * t)]
+        if len(set(samples)) == 2 * t:
+            break
+    xs, ys = samples[:t], samples[t:]
+    mds = [[pow((x + y) % P, P - 2, P) for y in ys] for x in xs]
+    return round_constants, mds
+
+
+def poseidon(inputs):
+    t = len(inputs) + 1
+    rp = PARTIAL_ROUNDS[t]
+    round_constants, mds = constants(t)
+    state = [0] + list(inputs)
+    for r in range(FULL_ROUNDS + rp):
+        state = [(s + round_constants[r * t + i]) % P for i, s in enumerate(state)]
+        if r < FULL_ROUNDS // 2 or r >= FULL_ROUNDS // 2 + rp:
+            state = [pow(s, 5, P) for s in state]
+        else:
+            state[0] = pow(state[0], 5, P)
+        state = [sum(m * s for m, s in zip(row, state)) % P for row in mds]
+    return state[0]
+
+
+# root of a tree with the given {index: leaf}, empty leaves are zero
+def merkle_root(depth, leaves):
+    zeros = [0]
+    for _ in range(depth):
+        zeros.append(poseidon([zeros[-1], zeros[-1]]))
+    layer = dict(leaves)
+    for level in range(depth):
+        layer = {
+            i: poseidon([layer.get(2 * i, zeros[level]), layer.get(2 * i + 1, zeros[level])])
+            for i in set(k // 2 for k in layer)
+        }
+    return layer.get(0, zeros[depth])
+
+
+def main():
+    d = str
+    out = {
+        "description": [
+            "Known answer vectors over BN254 for the circomlib Poseidon (x^5, 8 full rounds, capacity first).",
+            "source circomlibjs: outputs of circomlibjs poseidon, zk-kit: zero roots of the incremental merkle tree,",
+            "derived: merkle roots composed from those hashes by vectors/generate_kat.py.",
+            "rln (RLN v1) and rln_v2: witnesses and public inputs computed by zerokit (vacp2p/zerokit rln 0.3.4 and 0.5.1)",
+            "with vectors/zerokit/rln_v1.rs and rln_v2.rs, each source names the inputs. Field elements are decimal strings.",
+        ]
+    }
+
+    out["poseidon"] = [
+        {"source": "circomlibjs", "inputs": [d(x) for x in xs], "output": d(poseidon(xs))}
+        for xs in [[0], [1], [1, 2], [1, 2, 3], [1, 2, 3, 4], [1, 2, 3, 4, 5], [1, 2, 3, 4, 5, 6]]
+    ]
+
+    merkle = [
+        {"source": "zk-kit", "depth": depth, "leaves": [], "root": d(merkle_root(depth, {}))}
+        for depth in [1, 2, 3, 20]
+    ]
+    for depth, leaves in [(2, {0: 1, 1: 2, 2: 3, 3: 4}), (3, {5: 7}), (20, {0: 1, 9: 2, 1000: 3})]:
+        merkle.append(
+            {
+                "source": "derived",
+                "depth": depth,
+                "leaves": [{"index": k, "value": d(v)} for k, v in sorted(leaves.items())],
+                "root": d(merkle_root(depth, leaves)),
+            }
+        )
+    out["merkle"] = merkle
+
+    zerokit = os.path.join(os.path.dirname(os.path.abspath(__file__)), "zerokit")
+    for section, name in [("rln", "rln_v1.json"), ("rln_v2", "rln_v2.json")]:
+        with open(os.path.join(zerokit, name)) as f:
+            out[section] = json.load(f)
+
+    print(json.dumps(out, indent=2))
+
+
+if __name__ == "__main__":
+    main()
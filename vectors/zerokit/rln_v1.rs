@@ -0,0 +1,186 @@
+// RLN v1 known answer vectors computed by zerokit, the main.rs of a binary
+// depending on rln = "=0.3.4", zerokit_utils = "=0.3.2", ark-ff = "=0.4.1"
+// and serde_json, run as
+//
+//     cargo run --release > vectors/zerokit/rln_v1.json
+//
+// then regenerate vectors/kat.json with vectors/generate_kat.py.
+
+use ark_ff::{BigInteger, PrimeField};
+use rln::circuit::Fr;
+use rln::hashers::{hash_to_field, poseidon_hash};
+use rln::poseidon_tree::PoseidonTree;
+use rln::protocol::{
+    proof_values_from_witness, rln_witness_from_json, rln_witness_from_values, seeded_keygen,
+};
+use rln::public::RLN_IDENTIFIER;
+use rln::utils::str_to_fr;
+use serde_json::{json, Value};
+use zerokit_utils::{ZerokitMerkleProof, ZerokitMerkleTree};
+
+// witness of zerokit tests/protocol.rs, generated there with zk-kit
+// (https://github.com/oskarth/zk-kit/commit/b6a872f7160c7c14e10a0ea40acab99cbb23c9a8)
+const WITNESS_JSON_15: &str = r#"{
+  "identity_secret": "12825549237505733615964533204745049909430608936689388901883576945030025938736",
+  "path_elements": [
+    "18622655742232062119094611065896226799484910997537830749762961454045300666333",
+    "20590447254980891299813706518821659736846425329007960381537122689749540452732",
+    "7423237065226347324353380772367382631490014989348495481811164164159255474657",
+    "11286972368698509976183087595462810875513684078608517520839298933882497716792",
+    "3607627140608796879659380071776844901612302623152076817094415224584923813162",
+    "19712377064642672829441595136074946683621277828620209496774504837737984048981",
+    "20775607673010627194014556968476266066927294572720319469184847051418138353016",
+    "3396914609616007258851405644437304192397291162432396347162513310381425243293",
+    "21551820661461729022865262380882070649935529853313286572328683688269863701601",
+    "6573136701248752079028194407151022595060682063033565181951145966236778420039",
+    "12413880268183407374852357075976609371175688755676981206018884971008854919922",
+    "14271763308400718165336499097156975241954733520325982997864342600795471836726",
+    "20066985985293572387227381049700832219069292839614107140851619262827735677018",
+    "9394776414966240069580838672673694685292165040808226440647796406499139370960",
+    "11331146992410411304059858900317123658895005918277453009197229807340014528524"
+  ],
+  "identity_path_index": [
+    1,
+    1,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0
+  ],
+  "x": "8143228284048792769012135629627737459844825626241842423967352803501040982",
+  "epoch": "0x0000005b612540fc986b42322f8cb91c2273afad58ed006fdba0c97b4b16b12f",
+  "rln_identifier": "11412926387081627876309792396682864042420635853496105400039841573530884328439"
+}"#;
+
+fn dec(f: &Fr) -> String {
+    f.into_bigint().to_string()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn vector(
+    source: &str,
+    id_key: Fr,
+    path_elements: Vec<Fr>,
+    path_index: Vec<u8>,
+    only_leaf: bool,
+    x: Fr,
+    epoch: Fr,
+    rln_identifier: Fr,
+) -> Value {
+    let witness = rln_witness_from_json(
+        &json!({
+            "identity_secret": dec(&id_key),
+            "path_elements": path_elements.iter().map(dec).collect::<Vec<_>>(),
+            "identity_path_index": path_index,
+            "x": dec(&x),
+            "epoch": epoch
+                .into_bigint()
+                .to_bytes_be()
+                .iter()
+                .fold("0x".to_string(), |hex, b| format!("{}{:02x}", hex, b)),
+            "rln_identifier": dec(&rln_identifier),
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let values = proof_values_from_witness(&witness);
+    assert_eq!(values.rln_identifier, rln_identifier);
+    json!({
+        "source": source,
+        "depth": path_elements.len(),
+        "leaf_index": path_index.iter().rev().fold(0, |acc, b| 2 * acc + *b as usize),
+        "only_leaf": only_leaf,
+        "id_key": dec(&id_key),
+        "epoch": dec(&epoch),
+        "rln_identifier": dec(&rln_identifier),
+        "share_x": dec(&values.x),
+        "id_commitment": dec(&poseidon_hash(&[id_key])),
+        "path_elements": path_elements.iter().map(dec).collect::<Vec<_>>(),
+        "root": dec(&values.root),
+        "external_nullifier": dec(&poseidon_hash(&[epoch, rln_identifier])),
+        "share_y": dec(&values.y),
+        "nullifier": dec(&values.nullifier),
+    })
+}
+
+// witness of the member at leaf_index, the only one of a fresh tree
+fn member(source: &str, id_key: Fr, depth: usize, leaf_index: usize, x: Fr, epoch: Fr) -> Value {
+    let mut tree = PoseidonTree::new(depth, Fr::from(0), Default::default()).unwrap();
+    tree.set(leaf_index, poseidon_hash(&[id_key])).unwrap();
+    let proof = tree.proof(leaf_index).unwrap();
+    let witness = rln_witness_from_values(id_key, &proof, x, epoch);
+    assert_eq!(proof_values_from_witness(&witness).root, tree.root());
+    vector(
+        source,
+        id_key,
+        proof.get_path_elements(),
+        proof.get_path_index(),
+        true,
+        x,
+        epoch,
+        hash_to_field(RLN_IDENTIFIER),
+    )
+}
+
+fn main() {
+    let zk_kit: Value = serde_json::from_str(WITNESS_JSON_15).unwrap();
+    let fr = |v: &Value, radix| str_to_fr(v.as_str().unwrap(), radix).unwrap();
+    let vectors = vec![
+        vector(
+            "zerokit 0.3.4 tests/protocol.rs WITNESS_JSON_15, generated with zk-kit",
+            fr(&zk_kit["identity_secret"], 10),
+            zk_kit["path_elements"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| fr(e, 10))
+                .collect(),
+            zk_kit["identity_path_index"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| b.as_u64().unwrap() as u8)
+                .collect(),
+            false,
+            fr(&zk_kit["x"], 10),
+            fr(&zk_kit["epoch"], 16),
+            fr(&zk_kit["rln_identifier"], 10),
+        ),
+        // identity of zerokit test_merkle_proof, whose depth 20 root is
+        // 0x21947ffd0bce0c385f876e7c97d6a42eec5b1fe935aab2f01c1f8a8cbcc356d2
+        member(
+            "zerokit 0.3.4, id_key = hash_to_field(\"test-merkle-proof\"), x = hash_to_field(\"hey hey\"), epoch = hash_to_field(\"test-epoch\")",
+            hash_to_field(b"test-merkle-proof"),
+            20,
+            3,
+            hash_to_field(b"hey hey"),
+            hash_to_field(b"test-epoch"),
+        ),
+        member(
+            "zerokit 0.3.4, id_key of seeded_keygen(\"A seed phrase example\"), x = hash_to_field(\"hello world\")",
+            seeded_keygen(b"A seed phrase example").0,
+            10,
+            1000,
+            hash_to_field(b"hello world"),
+            Fr::from(1684080000u64),
+        ),
+    ];
+    assert_eq!(
+        vectors[1]["root"],
+        dec(&str_to_fr(
+            "0x21947ffd0bce0c385f876e7c97d6a42eec5b1fe935aab2f01c1f8a8cbcc356d2",
+            16
+        )
+        .unwrap())
+    );
+    println!("{}", serde_json::to_string_pretty(&vectors).unwrap());
+}
@@ -0,0 +1,121 @@
+// RLN v2 known answer vectors computed by zerokit, the main.rs of a binary
+// depending on rln = "=0.5.1", zerokit_utils = "=0.5.1", ark-ff = "=0.4.1"
+// and serde_json, run as
+//
+//     cargo run --release > vectors/zerokit/rln_v2.json
+//
+// then regenerate vectors/kat.json with vectors/generate_kat.py.
+
+use ark_ff::{BigInt, PrimeField};
+use rln::circuit::Fr;
+use rln::hashers::{hash_to_field, poseidon_hash};
+use rln::poseidon_tree::PoseidonTree;
+use rln::protocol::{proof_values_from_witness, rln_witness_from_values, seeded_keygen};
+use rln::public::RLN_IDENTIFIER;
+use serde_json::{json, Value};
+use zerokit_utils::{ZerokitMerkleProof, ZerokitMerkleTree};
+
+fn dec(f: &Fr) -> String {
+    f.into_bigint().to_string()
+}
+
+// witness of the member at leaf_index, the only one of a fresh tree
+#[allow(clippy::too_many_arguments)]
+fn member(
+    source: &str,
+    id_key: Fr,
+    user_message_limit: u64,
+    message_id: u64,
+    depth: usize,
+    leaf_index: usize,
+    x: Fr,
+    epoch: Fr,
+    rln_identifier: Fr,
+) -> Value {
+    let id_commitment = poseidon_hash(&[id_key]);
+    let rate_commitment = poseidon_hash(&[id_commitment, Fr::from(user_message_limit)]);
+    let mut tree = PoseidonTree::new(depth, Fr::from(0), Default::default()).unwrap();
+    tree.set(leaf_index, rate_commitment).unwrap();
+    let proof = tree.proof(leaf_index).unwrap();
+    let external_nullifier = poseidon_hash(&[epoch, rln_identifier]);
+    let witness = rln_witness_from_values(
+        id_key,
+        &proof,
+        x,
+        external_nullifier,
+        Fr::from(user_message_limit),
+        Fr::from(message_id),
+    )
+    .unwrap();
+    let values = proof_values_from_witness(&witness).unwrap();
+    assert_eq!(values.root, tree.root());
+    json!({
+        "source": source,
+        "depth": depth,
+        "leaf_index": leaf_index,
+        "only_leaf": true,
+        "id_key": dec(&id_key),
+        "user_message_limit": user_message_limit.to_string(),
+        "message_id": message_id.to_string(),
+        "epoch": dec(&epoch),
+        "rln_identifier": dec(&rln_identifier),
+        "share_x": dec(&values.x),
+        "id_commitment": dec(&id_commitment),
+        "rate_commitment": dec(&rate_commitment),
+        "path_elements": proof.get_path_elements().iter().map(dec).collect::<Vec<_>>(),
+        "root": dec(&values.root),
+        "external_nullifier": dec(&values.external_nullifier),
+        "share_y": dec(&values.y),
+        "nullifier": dec(&values.nullifier),
+    })
+}
+
+fn main() {
+    let vectors = vec![
+        // identity of zerokit test_merkle_proof and the epoch,
+        // rln_identifier and signal of its get_test_witness
+        member(
+            "zerokit 0.5.1, id_key = hash_to_field(\"test-merkle-proof\"), x = hash_to_field(\"hey hey\"), epoch = hash_to_field(\"test-epoch\"), rln_identifier = hash_to_field(\"test-rln-identifier\")",
+            hash_to_field(b"test-merkle-proof"),
+            100,
+            1,
+            20,
+            3,
+            hash_to_field(b"hey hey"),
+            hash_to_field(b"test-epoch"),
+            hash_to_field(b"test-rln-identifier"),
+        ),
+        member(
+            "zerokit 0.5.1, id_key of seeded_keygen(\"A seed phrase example\"), x = hash_to_field(\"hello world\"), rln_identifier = hash_to_field(RLN_IDENTIFIER)",
+            seeded_keygen(b"A seed phrase example").0,
+            10,
+            9,
+            20,
+            6,
+            hash_to_field(b"hello world"),
+            Fr::from(1684080000u64),
+            hash_to_field(RLN_IDENTIFIER),
+        ),
+        member(
+            "zerokit 0.5.1, id_key of seeded_keygen([0, 1, ..., 9]), x = hash_to_field(\"signal\")",
+            seeded_keygen(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]).0,
+            1,
+            0,
+            10,
+            1000,
+            hash_to_field(b"signal"),
+            Fr::from(42u64),
+            Fr::from(100u64),
+        ),
+    ];
+    // depth 20 root of zerokit tests/protocol.rs test_merkle_proof
+    let root: Fr = BigInt([
+        4939322235247991215,
+        5110804094006647505,
+        4427606543677101242,
+        910933464535675827,
+    ])
+    .into();
+    assert_eq!(vectors[0]["root"], dec(&root));
+    println!("{}", serde_json::to_string_pretty(&vectors).unwrap());
+}